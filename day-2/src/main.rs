@@ -1,5 +1,6 @@
 use std::env;
 use std::fs;
+use intcode::CompiledAutomaton;
 
 fn main() {
     let args : Vec<String> = env::args().collect();
@@ -8,7 +9,7 @@ fn main() {
 
     let contents = fs::read_to_string(path).expect("Failed to read contents of file");
 
-    let original_memory : Vec<i32> = parse_instructions(contents);
+    let original_memory : Vec<i64> = parse_instructions(contents);
 
     // Part One
     let mut program_memory = original_memory.clone();
@@ -31,76 +32,24 @@ fn main() {
     }
 }
 
-fn parse_instructions(contents : String) -> Vec<i32> {
-    contents.split(",").filter_map(|w| w.parse().ok()).collect()
+fn parse_instructions(contents : String) -> Vec<i64> {
+    intcode::parse_program(contents.as_str()).expect("Failed to parse program")
 }
 
-fn correct_program(instr : &mut Vec<i32>) -> () {
+fn correct_program(instr : &mut [i64]) {
     instr[1] = 12;
     instr[2] = 2;
 }
 
-fn alter_program(instr : &mut Vec<i32>, i : i32, j : i32) -> () {
+fn alter_program(instr : &mut [i64], i : i64, j : i64) {
     instr[1] = i;
     instr[2] = j;
 }
 
-fn run_program(instr : Vec<i32>) -> Vec<i32> {
-    let mut program = Program {
-        instructions : instr,
-        pc : 0,
-        finished : false,
-    };
-
-    while !program.finished {
-        program.do_operation();
-    }
-    program.instructions
-}
-
-struct Program {
-    instructions : Vec<i32>,
-    pc : usize,
-    finished : bool
-}
-
-impl Program {
-    fn next_operation(&mut self) -> &Self {
-        self.pc = self.pc + 4;
-        self
-    }
-
-    fn do_operation(&mut self) -> &Self {
-        match self.instructions.get(self.pc).unwrap() {
-            1 => self.op_add(),
-            2 => self.op_mult(),
-            99 => self.op_exit(),
-            _ => ()
-        }
-
-        if ! self.finished {
-            self.next_operation();
-        }
-        self
-    }
-
-    fn op_add(&mut self) {
-        let op1 = self.instructions.get(*self.instructions.get(self.pc + 1).unwrap() as usize).unwrap();
-        let op2 = self.instructions.get(*self.instructions.get(self.pc + 2).unwrap() as usize).unwrap();
-        let destination = *self.instructions.get(self.pc + 3).unwrap() as usize;
-        self.instructions[destination] = op1 + op2;
-    }
-
-    fn op_mult(&mut self) {
-        let op1 = self.instructions.get(*self.instructions.get(self.pc + 1).unwrap() as usize).unwrap();
-        let op2 = self.instructions.get(*self.instructions.get(self.pc + 2).unwrap() as usize).unwrap();
-        let destination = *self.instructions.get(self.pc + 3).unwrap() as usize;
-        self.instructions[destination] = op1 * op2;
-    }
-
-    fn op_exit(&mut self) {
-        self.finished = true;
-    }
+fn run_program(instr : Vec<i64>) -> Vec<i64> {
+    let mut automaton : CompiledAutomaton = CompiledAutomaton::new();
+    automaton.load_memory(instr).run_to_halt().expect("Program failed");
+    automaton.dump_memory().to_vec()
 }
 
 #[cfg(test)]
@@ -128,4 +77,4 @@ mod tests {
         assert_eq!(run_program(parse_instructions(String::from("1,1,1,4,99,5,6,0,99"))), vec![30,1,1,4,2,5,6,0,99]);
     }
 
-}
\ No newline at end of file
+}
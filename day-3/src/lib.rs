@@ -0,0 +1,56 @@
+/// A direction on the grid, with `Up` towards increasing y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn turn_left(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+
+    pub fn turn_right(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    /// The coordinate one step from `coord` in this direction.
+    pub fn step(&self, coord : (i32,i32)) -> (i32,i32) {
+        match self {
+            Direction::Up => (coord.0, coord.1 + 1),
+            Direction::Down => (coord.0, coord.1 - 1),
+            Direction::Left => (coord.0 - 1, coord.1),
+            Direction::Right => (coord.0 + 1, coord.1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Direction;
+
+    #[test]
+    fn test_turns() {
+        assert_eq!(Direction::Up.turn_left().turn_left(), Direction::Down);
+        assert_eq!(Direction::Left.turn_right(), Direction::Up);
+        assert_eq!(Direction::Right.turn_left().turn_right(), Direction::Right);
+    }
+
+    #[test]
+    fn test_step() {
+        assert_eq!(Direction::Up.step((0,0)), (0,1));
+        assert_eq!(Direction::Left.step(Direction::Down.step((3,3))), (2,2));
+    }
+}
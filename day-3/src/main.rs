@@ -1,6 +1,7 @@
 use std::{env, fs};
 use std::collections::HashSet;
 use std::iter::FromIterator;
+use day_3::Direction;
 
 fn main() {
     let args : Vec<String> = env::args().collect();
@@ -43,12 +44,7 @@ fn generate_maps(wires: &Vec<Vec<Move>>) -> Vec<Vec<(i32,i32)>> {
         let mut coord : (i32,i32) = (0,0);
         for mv in wire {
             for _ in 1..=mv.amount {
-                match mv.direction {
-                    Direction::Up => {coord.1 += 1},
-                    Direction::Down => {coord.1 += -1}
-                    Direction::Left => {coord.0 += -1}
-                    Direction::Right => {coord.0 += 1}
-                };
+                coord = mv.direction.step(coord);
                 path.push(coord);
             }
         }
@@ -101,14 +97,6 @@ fn find_nearest_intersection(maps : &Vec<Vec<(i32,i32)>>, intersections : &HashS
     nearest as i32
 }
 
-#[derive(Debug)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
 #[derive(Debug)]
 struct Move {
     direction : Direction,
@@ -0,0 +1,205 @@
+use crate::{Automaton, Cell, ErrorKind, IntcodeError, Status};
+
+/// A series of machines running the same program, where each machine's
+/// output is the next machine's input. Every machine first reads its phase
+/// setting, then the signal coming from the previous machine.
+pub struct AmplifierChain<T = i64> {
+    program : Vec<T>,
+}
+
+impl<T : Cell> AmplifierChain<T> {
+    pub fn new(program : Vec<T>) -> AmplifierChain<T> {
+        AmplifierChain { program }
+    }
+
+    fn boot(&self, phases : &[T]) -> Vec<Automaton<T>> {
+        phases.iter().map(|phase| {
+            let mut automaton = Automaton::new();
+            automaton.load_memory(self.program.clone()).push_input(phase.clone());
+            automaton
+        }).collect()
+    }
+
+    /// Passes `input` through every machine once and returns the signal the
+    /// last machine outputs.
+    pub fn run_once(&self, phases : &[T], input : T) -> Result<T, IntcodeError> {
+        let mut signal = input;
+        for automaton in self.boot(phases).iter_mut() {
+            automaton.push_input(signal);
+            signal = next_signal(automaton)?;
+        }
+        Ok(signal)
+    }
+
+    /// Feeds the last machine's output back into the first machine until
+    /// every machine has halted, and returns the last signal the last machine
+    /// sent to the thrusters. A machine that halts passes nothing on; if the
+    /// remaining machines then all wait for a signal, that is an error.
+    pub fn run_feedback(&self, phases : &[T], input : T) -> Result<T, IntcodeError> {
+        let mut automata = self.boot(phases);
+        let last = match automata.len() {
+            0 => return Ok(input),
+            length => length - 1,
+        };
+        let mut signal = Some(input);
+        let mut thrusters = None;
+        loop {
+            let mut progress = false;
+            for (index, automaton) in automata.iter_mut().enumerate() {
+                if automaton.is_finished() {
+                    signal = None;
+                    continue;
+                }
+                if let Some(value) = signal.take() {
+                    automaton.push_input(value);
+                }
+                match automaton.run()? {
+                    Status::Output(output) => {
+                        if index == last {
+                            thrusters = Some(output.clone());
+                        }
+                        signal = Some(output);
+                        progress = true;
+                    },
+                    Status::Halted => progress = true,
+                    _ => (),
+                }
+            }
+
+            if automata.iter().all(|automaton| automaton.is_finished()) {
+                return thrusters.ok_or_else(|| automata[last].error(ErrorKind::NoOutput));
+            }
+            if !progress {
+                let waiting = automata.iter().find(|automaton| !automaton.is_finished()).unwrap();
+                return Err(waiting.error(ErrorKind::NoInput));
+            }
+        }
+    }
+
+    /// Tries every ordering of `phases` in a one-shot chain and returns the
+    /// highest signal together with the ordering that produced it.
+    pub fn max_signal(&self, phases : &[T]) -> Result<(T, Vec<T>), IntcodeError> {
+        self.search(phases, |order| self.run_once(order, T::zero()))
+    }
+
+    /// Like `max_signal`, but with the machines connected in a feedback loop.
+    pub fn max_feedback_signal(&self, phases : &[T]) -> Result<(T, Vec<T>), IntcodeError> {
+        self.search(phases, |order| self.run_feedback(order, T::zero()))
+    }
+
+    fn search<F>(&self, phases : &[T], run : F) -> Result<(T, Vec<T>), IntcodeError>
+        where F : Fn(&[T]) -> Result<T, IntcodeError> {
+        let mut best : Option<(T, Vec<T>)> = None;
+        for order in permutations(phases) {
+            let signal = run(&order)?;
+            let better = match &best {
+                Some((best_signal, _)) => signal > *best_signal,
+                None => true,
+            };
+            if better {
+                best = Some((signal, order));
+            }
+        }
+        Ok(best.unwrap_or_else(|| (T::zero(), vec![])))
+    }
+}
+
+/// Runs a machine until it outputs, halting without output is an error.
+fn next_signal<T : Cell>(automaton : &mut Automaton<T>) -> Result<T, IntcodeError> {
+    match automaton.run()? {
+        Status::Output(signal) => Ok(signal),
+        Status::NeedsInput => Err(automaton.error(ErrorKind::NoInput)),
+        _ => Err(automaton.error(ErrorKind::NoOutput)),
+    }
+}
+
+/// All orderings of `items`, generated with Heap's algorithm.
+pub fn permutations<T : Clone>(items : &[T]) -> Vec<Vec<T>> {
+    let mut items = items.to_vec();
+    let mut result = vec![items.clone()];
+    let mut counters = vec![0; items.len()];
+    let mut i = 1;
+    while i < items.len() {
+        if counters[i] < i {
+            let swap_with = if i % 2 == 0 { 0 } else { counters[i] };
+            items.swap(swap_with, i);
+            result.push(items.clone());
+            counters[i] += 1;
+            i = 1;
+        } else {
+            counters[i] = 0;
+            i += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assemble_cells, parse_program, permutations, AmplifierChain, ErrorKind};
+
+    #[test]
+    fn test_permutations() {
+        let mut all = permutations(&[1, 2, 3]);
+        all.sort();
+        assert_eq!(all, vec![vec![1,2,3], vec![1,3,2], vec![2,1,3], vec![2,3,1], vec![3,1,2], vec![3,2,1]]);
+        assert_eq!(permutations(&[0, 1, 2, 3, 4]).len(), 120);
+    }
+
+    #[test]
+    fn test_run_once() {
+        let chain = AmplifierChain::new(parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap());
+        assert_eq!(chain.run_once(&[4, 3, 2, 1, 0], 0).unwrap(), 43210);
+    }
+
+    #[test]
+    fn test_max_signal() {
+        let chain = AmplifierChain::new(parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap());
+        assert_eq!(chain.max_signal(&[0, 1, 2, 3, 4]).unwrap(), (43210, vec![4, 3, 2, 1, 0]));
+
+        let chain = AmplifierChain::new(parse_program("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0").unwrap());
+        assert_eq!(chain.max_signal(&[0, 1, 2, 3, 4]).unwrap(), (54321, vec![0, 1, 2, 3, 4]));
+
+        let chain = AmplifierChain::new(parse_program("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0").unwrap());
+        assert_eq!(chain.max_signal(&[0, 1, 2, 3, 4]).unwrap(), (65210, vec![1, 0, 4, 3, 2]));
+    }
+
+    #[test]
+    fn test_max_feedback_signal() {
+        let chain = AmplifierChain::new(parse_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap());
+        assert_eq!(chain.max_feedback_signal(&[5, 6, 7, 8, 9]).unwrap(), (139629729, vec![9, 8, 7, 6, 5]));
+
+        let chain = AmplifierChain::new(parse_program("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10").unwrap());
+        assert_eq!(chain.max_feedback_signal(&[5, 6, 7, 8, 9]).unwrap(), (18216, vec![9, 7, 8, 5, 6]));
+    }
+
+    #[test]
+    fn test_feedback_until_all_halted() {
+        // passes on signal + 1 as many times as its phase setting, then halts
+        let chain = AmplifierChain::new(assemble_cells("
+                    in [phase]
+            loop:   in [signal]
+                    add [signal], #1, [signal]
+                    out [signal]
+                    add [count], #1, [count]
+                    eq [count], [phase], [done]
+                    jf [done], #loop
+                    hlt
+            phase:  db 0
+            signal: db 0
+            count:  db 0
+            done:   db 0
+        ").unwrap());
+        // the middle machine halts first; the first one still runs a second round
+        assert_eq!(chain.run_feedback(&[2, 1, 1], 0).unwrap(), 3);
+        // the last machine is left waiting for a signal that never comes
+        assert_eq!(chain.run_feedback(&[2, 1, 2], 0).unwrap_err().kind, ErrorKind::NoInput);
+    }
+
+    #[test]
+    fn test_machine_without_output() {
+        let chain = AmplifierChain::new(parse_program("3,0,3,0,99").unwrap());
+        let error = chain.run_once(&[1, 2], 0).err().unwrap();
+        assert_eq!(error.kind, ErrorKind::NoOutput);
+    }
+}
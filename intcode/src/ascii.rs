@@ -0,0 +1,177 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use crate::{Automaton, Cell, IntcodeError, Status};
+
+/// A piece of a machine's output: a run of ASCII characters, or a value
+/// outside the ASCII range, which programs use to report numeric answers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsciiOutput<T = i64> {
+    Text(String),
+    Value(T),
+}
+
+impl<T : fmt::Display> fmt::Display for AsciiOutput<T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsciiOutput::Text(text) => write!(f, "{}", text),
+            AsciiOutput::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Decodes output values into text, keeping values outside the ASCII
+/// range as numbers.
+pub fn decode_ascii<T : Cell>(values : &[T]) -> Vec<AsciiOutput<T>> {
+    let mut decoded = Vec::new();
+    for value in values {
+        let ascii = value.to_i64().filter(|code| (0..128).contains(code)).map(|code| code as u8 as char);
+        match (ascii, decoded.last_mut()) {
+            (Some(c), Some(AsciiOutput::Text(text))) => text.push(c),
+            (Some(c), _) => decoded.push(AsciiOutput::Text(c.to_string())),
+            (None, _) => decoded.push(AsciiOutput::Value(value.clone())),
+        }
+    }
+    decoded
+}
+
+/// Encodes a line of text, followed by a newline, as input values.
+pub fn encode_line<T : Cell>(line : &str) -> Vec<T> {
+    line.chars().chain(Some('\n'))
+        .map(|c| T::from_i64(c as i64).expect("Character codes fit in every cell type"))
+        .collect()
+}
+
+/// An `Automaton` running a program that talks in ASCII: it prints text and
+/// reads lines. `run` returns the decoded output; `terminal` lets a user
+/// converse with the program.
+pub struct AsciiAutomaton<T = i64> {
+    automaton : Automaton<T>,
+}
+
+impl<T : Cell> AsciiAutomaton<T> {
+    pub fn from_automaton(automaton : Automaton<T>) -> AsciiAutomaton<T> {
+        AsciiAutomaton { automaton }
+    }
+
+    pub fn get_automaton(&self) -> &Automaton<T> {
+        &self.automaton
+    }
+
+    pub fn get_automaton_mut(&mut self) -> &mut Automaton<T> {
+        &mut self.automaton
+    }
+
+    pub fn into_automaton(self) -> Automaton<T> {
+        self.automaton
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.automaton.is_finished()
+    }
+
+    /// Queues a line of input; the newline is added.
+    pub fn send_line(&mut self, line : &str) -> &mut Self {
+        self.automaton.push_inputs(encode_line(line));
+
+        self
+    }
+
+    /// Runs until the machine waits for a line or halts, and returns what
+    /// it printed.
+    pub fn run(&mut self) -> Result<Vec<AsciiOutput<T>>, IntcodeError> {
+        loop {
+            match self.automaton.run()? {
+                Status::NeedsInput | Status::Halted => break,
+                Status::Output(_) | Status::Running | Status::Watchpoint(_) => (),
+            }
+        }
+        Ok(decode_ascii(&self.automaton.take_output()))
+    }
+
+    /// Shows the program's output on `output` and sends it each line read
+    /// from `input`, until the program halts or the input ends. Values
+    /// outside the ASCII range are shown on a line of their own.
+    pub fn terminal<R : BufRead, W : Write>(&mut self, input : R, mut output : W) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            match self.run() {
+                Ok(decoded) => {
+                    for piece in decoded {
+                        match piece {
+                            AsciiOutput::Text(text) => write!(output, "{}", text)?,
+                            AsciiOutput::Value(value) => writeln!(output, "{}", value)?,
+                        }
+                    }
+                },
+                Err(error) => {
+                    writeln!(output, "error: {}", error)?;
+                    break;
+                },
+            }
+            output.flush()?;
+            if self.is_finished() {
+                break;
+            }
+            match lines.next() {
+                Some(line) => self.send_line(&line?),
+                None => break,
+            };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assemble_cells, decode_ascii, encode_line, AsciiAutomaton, AsciiOutput, Automaton};
+
+    /// Asks for a line, echoes it and reports the number 1000.
+    const ECHO : &str = "
+                out #63
+                out #10
+        loop:   in [c]
+                out [c]
+                eq [c], #10, [nl]
+                jf [nl], #loop
+                out #1000
+                hlt
+        c:      db 0
+        nl:     db 0
+    ";
+
+    fn echo() -> AsciiAutomaton {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load_memory(assemble_cells(ECHO).unwrap());
+        AsciiAutomaton::from_automaton(automaton)
+    }
+
+    #[test]
+    fn test_decode_and_encode() {
+        let values : Vec<i64> = vec![72, 105, 10, 19349974, 46, -1];
+        assert_eq!(decode_ascii(&values), vec![
+            AsciiOutput::Text(String::from("Hi\n")),
+            AsciiOutput::Value(19349974),
+            AsciiOutput::Text(String::from(".")),
+            AsciiOutput::Value(-1),
+        ]);
+        let encoded : Vec<i64> = encode_line("WALK");
+        assert_eq!(encoded, vec![87, 65, 76, 75, 10]);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut machine = echo();
+        assert_eq!(machine.run().unwrap(), vec![AsciiOutput::Text(String::from("?\n"))]);
+        assert!(!machine.is_finished());
+        let decoded = machine.send_line("hi").run().unwrap();
+        assert_eq!(decoded, vec![AsciiOutput::Text(String::from("hi\n")), AsciiOutput::Value(1000)]);
+        assert!(machine.is_finished());
+    }
+
+    #[test]
+    fn test_terminal() {
+        let mut output = Vec::new();
+        echo().terminal("hello\nignored\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "?\nhello\n1000\n");
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use crate::INSTRUCTION_SET;
+
+/// A problem in the assembly source, with the 1-based line it occurred on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line : usize,
+    pub message : String,
+}
+
+impl AssembleError {
+    fn new(line : usize, message : String) -> AssembleError {
+        AssembleError { line, message }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+/// A number, or a label plus an optional offset such as `buffer+2`.
+enum Value {
+    Number(i64),
+    Label(String, i64),
+}
+
+enum Operand {
+    Position(Value),
+    Immediate(Value),
+    Relative(i64),
+}
+
+enum Statement {
+    Instruction { opcode : i8, operands : Vec<Operand> },
+    Data(Vec<Value>),
+}
+
+/// Assembles source text into the comma-separated image `Automaton::load` accepts.
+///
+/// Each line holds an optional `label:`, then an instruction or `db`
+/// directive; `;` starts a comment. Operands are written the way the
+/// disassembler lists them: `[addr]` for position mode, `#value` for
+/// immediate mode and `rb+off`/`rb-off` for relative mode. Addresses and
+/// values may be labels, optionally with an offset (`[table+2]`).
+///
+/// ```text
+/// loop:   in [value]
+///         out [value]
+///         jt #1, #loop
+/// value:  db 0
+/// ```
+pub fn assemble(source : &str) -> Result<String, AssembleError> {
+    let cells = assemble_cells(source)?;
+    Ok(cells.iter().map(|cell| cell.to_string()).collect::<Vec<String>>().join(","))
+}
+
+/// Like `assemble`, but returns the memory cells of the image.
+pub fn assemble_cells(source : &str) -> Result<Vec<i64>, AssembleError> {
+    let mut labels : HashMap<String, usize> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut text = text.split(';').next().unwrap_or("").trim();
+
+        if let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_identifier(label) {
+                return Err(AssembleError::new(line, format!("invalid label '{}'", label)));
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(AssembleError::new(line, format!("duplicate label '{}'", label)));
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(text).map_err(|message| AssembleError::new(line, message))?;
+        address += match &statement {
+            Statement::Instruction { operands, .. } => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        };
+        statements.push((line, statement));
+    }
+
+    let resolve = |line : usize, value : &Value| -> Result<i64, AssembleError> {
+        match value {
+            Value::Number(number) => Ok(*number),
+            Value::Label(label, offset) => labels.get(label)
+                .map(|address| *address as i64 + offset)
+                .ok_or_else(|| AssembleError::new(line, format!("unknown label '{}'", label))),
+        }
+    };
+
+    let mut cells = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        match statement {
+            Statement::Instruction { opcode, operands } => {
+                let mut instruction = i64::from(opcode);
+                let mut mode_factor = 100;
+                let mut params = Vec::new();
+                for operand in &operands {
+                    let (mode, param) = match operand {
+                        Operand::Position(value) => (0, resolve(line, value)?),
+                        Operand::Immediate(value) => (1, resolve(line, value)?),
+                        Operand::Relative(offset) => (2, *offset),
+                    };
+                    instruction += mode * mode_factor;
+                    mode_factor *= 10;
+                    params.push(param);
+                }
+                cells.push(instruction);
+                cells.extend(params);
+            },
+            Statement::Data(values) => {
+                for value in &values {
+                    cells.push(resolve(line, value)?);
+                }
+            },
+        }
+    }
+    Ok(cells)
+}
+
+fn parse_statement(text : &str) -> Result<Statement, String> {
+    let (mnemonic, rest) = match text.find(char::is_whitespace) {
+        Some(split) => (&text[..split], text[split..].trim()),
+        None => (text, ""),
+    };
+    let arguments : Vec<&str> = if rest.is_empty() {
+        vec![]
+    } else {
+        rest.split(',').map(|argument| argument.trim()).collect()
+    };
+
+    if mnemonic == "db" {
+        if arguments.is_empty() {
+            return Err(String::from("db needs at least one value"));
+        }
+        let values = arguments.iter().map(|argument| parse_value(argument)).collect::<Result<_, _>>()?;
+        return Ok(Statement::Data(values));
+    }
+
+    let def = INSTRUCTION_SET.iter().find(|def| def.mnemonic == mnemonic)
+        .ok_or_else(|| format!("unknown mnemonic '{}'", mnemonic))?;
+    if arguments.len() != def.no_params as usize {
+        return Err(format!("{} takes {} operands, got {}", mnemonic, def.no_params, arguments.len()));
+    }
+    let operands : Vec<Operand> = arguments.iter().map(|argument| parse_operand(argument)).collect::<Result<_, _>>()?;
+    if let Some(index) = def.write_param {
+        if let Operand::Immediate(_) = operands[index] {
+            return Err(format!("{} cannot write to immediate operand '{}'", mnemonic, arguments[index]));
+        }
+    }
+    Ok(Statement::Instruction { opcode : def.opcode, operands })
+}
+
+fn parse_operand(text : &str) -> Result<Operand, String> {
+    if let Some(value) = text.strip_prefix('#') {
+        Ok(Operand::Immediate(parse_value(value)?))
+    } else if text.starts_with('[') && text.ends_with(']') {
+        Ok(Operand::Position(parse_value(&text[1..text.len() - 1])?))
+    } else if let Some(offset) = text.strip_prefix("rb") {
+        let offset = offset.replace(' ', "");
+        if offset.is_empty() {
+            return Ok(Operand::Relative(0));
+        }
+        // exactly one sign, followed by digits only
+        let magnitude = offset.get(1..).filter(|digits| digits.starts_with(|c : char| c.is_ascii_digit()))
+            .and_then(|digits| digits.parse::<i64>().ok());
+        match (offset.chars().next(), magnitude) {
+            (Some('+'), Some(magnitude)) => Ok(Operand::Relative(magnitude)),
+            (Some('-'), Some(magnitude)) => Ok(Operand::Relative(-magnitude)),
+            _ => Err(format!("invalid relative operand '{}'", text)),
+        }
+    } else {
+        Err(format!("operand '{}' needs a mode: [address], #value or rb+offset", text))
+    }
+}
+
+fn parse_value(text : &str) -> Result<Value, String> {
+    let text = text.trim();
+    if let Ok(number) = text.parse::<i64>() {
+        return Ok(Value::Number(number));
+    }
+
+    let split = text.find(['+', '-']).unwrap_or(text.len());
+    let (label, offset) = text.split_at(split);
+    let label = label.trim();
+    let offset = match offset.replace(' ', "").as_str() {
+        "" => 0,
+        offset => offset.trim_start_matches('+').parse::<i64>()
+            .map_err(|_| format!("invalid offset in '{}'", text))?,
+    };
+    if !is_identifier(label) {
+        return Err(format!("invalid value '{}'", text));
+    }
+    Ok(Value::Label(label.to_string(), offset))
+}
+
+fn is_identifier(text : &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' =>
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assemble, assemble_cells, disassemble, parse_program, AssembleError, Automaton, Line};
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(assemble("mul [4], #3, [4]\ndb 33").unwrap(), "1002,4,3,4,33");
+        assert_eq!(assemble("add #100, #-1, [4]\n db 0").unwrap(), "1101,100,-1,4,0");
+        assert_eq!(assemble("arb #1\nout rb-1\nhlt").unwrap(), "109,1,204,-1,99");
+        assert_eq!(assemble("add rb+1, rb, rb+2").unwrap(), "22201,1,0,2");
+    }
+
+    #[test]
+    fn test_labels_and_comments() {
+        let source = "
+            ; echo every input back
+            loop:   in [value]
+                    out [value]     ; copy it to the output
+                    jt #1, #loop
+            value:  db 0
+            table:  db value, table+1, loop-1
+        ";
+        assert_eq!(assemble(source).unwrap(), "3,7,4,7,1105,1,0,0,7,9,-1");
+
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load(&assemble(source).unwrap()).unwrap().push_inputs(vec![4, 2]);
+        automaton.run().unwrap();
+        automaton.run().unwrap();
+        assert_eq!(automaton.get_output(), &[4, 2]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("hlt\nfoo [1]").err().unwrap(), AssembleError { line : 2, message : String::from("unknown mnemonic 'foo'") });
+        assert_eq!(assemble("add #1, #2").err().unwrap().message, "add takes 3 operands, got 2");
+        assert_eq!(assemble("out 5").err().unwrap().message, "operand '5' needs a mode: [address], #value or rb+offset");
+        assert_eq!(assemble("jt #1, #nowhere").err().unwrap().message, "unknown label 'nowhere'");
+        assert_eq!(assemble("a: hlt\na: hlt").err().unwrap().message, "duplicate label 'a'");
+        assert_eq!(assemble("out rb*2").err().unwrap().message, "invalid relative operand 'rb*2'");
+        assert_eq!(assemble("out rb+-3").err().unwrap().message, "invalid relative operand 'rb+-3'");
+        assert_eq!(assemble("out rb-+3").err().unwrap().message, "invalid relative operand 'rb-+3'");
+        assert_eq!(assemble("out rb--3").err().unwrap().message, "invalid relative operand 'rb--3'");
+        assert_eq!(assemble("db").err().unwrap().message, "db needs at least one value");
+        assert_eq!(assemble("hlt\nadd #1, #2, #3\nhlt").err().unwrap(),
+                   AssembleError { line : 2, message : String::from("add cannot write to immediate operand '#3'") });
+        assert_eq!(assemble("in #0").err().unwrap().message, "in cannot write to immediate operand '#0'");
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        let image : Vec<i64> = parse_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap();
+        let source : Vec<String> = disassemble(&image).iter().map(|line| match line {
+            Line::Code { instruction, .. } => instruction.to_string(),
+            Line::Data { values, .. } => format!("db {}", values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", ")),
+        }).collect();
+        assert_eq!(assemble_cells(&source.join("\n")).unwrap(), image);
+    }
+}
@@ -0,0 +1,162 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use crate::network::SLICE;
+use crate::{Automaton, Cell, ErrorKind, IntcodeError, Status};
+
+/// An `Automaton` that awaits its input from a stream and sends its output
+/// to a sink, instead of pausing when its input queue is empty. Machines,
+/// and whatever else they talk to, run as tasks on one executor, e.g.
+/// `futures::executor::LocalPool`, connected by `futures::channel::mpsc`.
+///
+/// Inputs already queued on the automaton are read before the stream.
+pub struct AsyncAutomaton<T = i64> {
+    automaton : Automaton<T>,
+}
+
+impl<T : Cell> AsyncAutomaton<T> {
+    pub fn from_automaton(automaton : Automaton<T>) -> AsyncAutomaton<T> {
+        AsyncAutomaton { automaton }
+    }
+
+    pub fn get_automaton(&self) -> &Automaton<T> {
+        &self.automaton
+    }
+
+    pub fn into_automaton(self) -> Automaton<T> {
+        self.automaton
+    }
+
+    /// Runs the machine until it halts. Fails with `ErrorKind::NoInput` when
+    /// the input stream ends while the machine reads from it, and with
+    /// `ErrorKind::OutputClosed` when the output sink rejects a value.
+    pub async fn run<I, O>(&mut self, mut input : I, mut output : O) -> Result<(), IntcodeError>
+            where I : Stream<Item = T> + Unpin, O : Sink<T> + Unpin {
+        let mut executed = 0;
+        loop {
+            let status = self.automaton.step()?;
+            // an output can also arrive with a watchpoint pausing the machine
+            for value in self.automaton.take_output() {
+                if output.send(value).await.is_err() {
+                    return Err(self.automaton.error(ErrorKind::OutputClosed));
+                }
+            }
+            match status {
+                Status::NeedsInput => match input.next().await {
+                    Some(value) => {
+                        self.automaton.push_input(value);
+                    },
+                    None => return Err(self.automaton.error(ErrorKind::NoInput)),
+                },
+                Status::Halted => return Ok(()),
+                Status::Output(_) | Status::Running | Status::Watchpoint(_) => (),
+            }
+
+            executed += 1;
+            if executed == SLICE {
+                executed = 0;
+                YieldNow(false).await;
+            }
+        }
+    }
+}
+
+/// Returns `Pending` once, after asking to be polled again.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self : Pin<&mut Self>, context : &mut Context) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        context.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use futures::channel::mpsc;
+    use futures::executor::{block_on, LocalPool};
+    use futures::task::LocalSpawnExt;
+    use futures::{stream, SinkExt, StreamExt};
+    use crate::{parse_program, AsyncAutomaton, Automaton, ErrorKind, WatchKind, Watchpoint};
+
+    #[test]
+    fn test_stream_and_sink() {
+        // doubles every input until it reads 0
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0").unwrap().push_input(5);
+        let mut machine = AsyncAutomaton::from_automaton(automaton);
+        let mut output = Vec::new();
+        block_on(machine.run(stream::iter(vec![7, 0]), &mut output)).unwrap();
+        assert_eq!(output, vec![10, 14]);
+    }
+
+    #[test]
+    fn test_output_at_watchpoint() {
+        // the read of the output's operand pauses the machine right before it halts
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("4,3,99,42").unwrap().add_watchpoint(Watchpoint::new(3..4, WatchKind::Read));
+        let mut machine = AsyncAutomaton::from_automaton(automaton);
+        let mut output = Vec::new();
+        block_on(machine.run(stream::empty(), &mut output)).unwrap();
+        assert_eq!(output, vec![42]);
+        assert!(machine.get_automaton().get_output().is_empty());
+    }
+
+    #[test]
+    fn test_input_ends() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,0,99").unwrap();
+        let mut machine = AsyncAutomaton::from_automaton(automaton);
+        let error = block_on(machine.run(stream::empty(), Vec::new())).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NoInput);
+    }
+
+    #[test]
+    fn test_feedback_loop_with_component() {
+        let program : Vec<i64> = parse_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+            27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap();
+        let mut pool = LocalPool::new();
+        let spawner = pool.spawner();
+
+        // machine i reads from channel i and writes to channel i + 1; a
+        // non-Intcode task closes the loop from channel 5 back to channel 0
+        let (senders, receivers) : (Vec<_>, Vec<_>) = (0..6).map(|_| mpsc::unbounded::<i64>()).unzip();
+        let mut receivers = receivers.into_iter();
+        for (machine, phase) in [9, 8, 7, 6, 5].iter().enumerate() {
+            let mut automaton : Automaton = Automaton::new();
+            automaton.load_memory(program.clone()).push_input(*phase);
+            let mut automaton = AsyncAutomaton::from_automaton(automaton);
+            let (input, output) = (receivers.next().unwrap(), senders[machine + 1].clone());
+            spawner.spawn_local(async move {
+                automaton.run(input, output).await.unwrap();
+            }).unwrap();
+        }
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let (mut feedback, mut last) = (senders[0].clone(), receivers.next().unwrap());
+        let seen = signals.clone();
+        spawner.spawn_local(async move {
+            while let Some(signal) = last.next().await {
+                seen.borrow_mut().push(signal);
+                // the first machine has halted after the last signal
+                let _ = feedback.send(signal).await;
+            }
+        }).unwrap();
+
+        let mut start = senders[0].clone();
+        drop(senders);
+        block_on(start.send(0)).unwrap();
+        drop(start);
+        pool.run();
+        assert_eq!(signals.borrow().last(), Some(&139629729));
+    }
+}
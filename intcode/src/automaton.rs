@@ -0,0 +1,1489 @@
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Instant;
+use std::fmt;
+use crate::history::{History, UndoRecord};
+use crate::{Cell, Coverage, ErrorKind, IntcodeError, Memory, ParseError, Profile, Snapshot, TraceEvent, Tracer, WatchEvent, Watchpoint};
+
+/// Parses a comma-separated Intcode image into memory cells. Fails on the
+/// first cell that is not a number or does not fit in the cell type; input
+/// that is only whitespace is an empty image.
+pub fn parse_program<T : Cell>(input : &str) -> Result<Vec<T>, ParseError> {
+    if input.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    input.split(',').enumerate()
+        .map(|(index, token)| token.trim().parse().map_err(|_| ParseError { index, token : token.trim().to_string() }))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionDef {
+    pub opcode : i8,
+    pub no_params : i8,
+    pub mnemonic : &'static str,
+    /// Index of the parameter the instruction writes to, if any.
+    pub write_param : Option<usize>,
+}
+
+/// Number of cells taken by the longest instruction.
+const MAX_INSTRUCTION_LENGTH : usize = 4;
+
+/// The complete Intcode instruction set.
+pub const INSTRUCTION_SET : [InstructionDef; 10] = [
+    InstructionDef {opcode : 1, no_params : 3, mnemonic : "add", write_param : Some(2)},
+    InstructionDef {opcode : 2, no_params : 3, mnemonic : "mul", write_param : Some(2)},
+    InstructionDef {opcode : 3, no_params : 1, mnemonic : "in", write_param : Some(0)},
+    InstructionDef {opcode : 4, no_params : 1, mnemonic : "out", write_param : None},
+
+    InstructionDef {opcode : 5, no_params : 2, mnemonic : "jt", write_param : None},
+    InstructionDef {opcode : 6, no_params : 2, mnemonic : "jf", write_param : None},
+    InstructionDef {opcode : 7, no_params : 3, mnemonic : "lt", write_param : Some(2)},
+    InstructionDef {opcode : 8, no_params : 3, mnemonic : "eq", write_param : Some(2)},
+    InstructionDef {opcode : 9, no_params : 1, mnemonic : "arb", write_param : None},
+
+    InstructionDef {opcode : 99, no_params : 0, mnemonic : "hlt", write_param : None},
+];
+
+pub fn get_instruction_def(opcode : i8) -> Option<&'static InstructionDef> {
+    INSTRUCTION_SET.iter().find(|def| def.opcode == opcode)
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction<T = i64> {
+    pub opcode : i8,
+    pub params : Vec<Parameter<T>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter<T = i64> {
+    pub param : T,
+    pub mode : ParameterMode,
+}
+
+/// Why `step` or `run` returned control to the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status<T = i64> {
+    /// The instruction executed and the machine can continue.
+    Running,
+    /// The next instruction reads input but the queue is empty; push input and resume.
+    NeedsInput,
+    /// The instruction just executed produced this value.
+    Output(T),
+    /// The instruction just executed hit a pausing watchpoint. If it hit
+    /// several, this is the last one; all of them are in the watch log.
+    /// Any output the instruction produced is still in the output buffer.
+    Watchpoint(WatchEvent<T>),
+    Halted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterMode {
+    Position = 0,
+    Immediate = 1,
+    Relative = 2,
+}
+
+impl<T : fmt::Display> fmt::Display for Parameter<T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.param),
+            ParameterMode::Immediate => write!(f, "#{}", self.param),
+            ParameterMode::Relative => {
+                let offset = self.param.to_string();
+                match offset.strip_prefix('-') {
+                    Some(magnitude) => write!(f, "rb-{}", magnitude),
+                    None => write!(f, "rb+{}", offset),
+                }
+            },
+        }
+    }
+}
+
+impl<T : fmt::Display> fmt::Display for Instruction<T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match get_instruction_def(self.opcode) {
+            Some(def) => write!(f, "{}", def.mnemonic)?,
+            None => write!(f, "op{}", self.opcode)?,
+        }
+        for (i, param) in self.params.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, param)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Automaton<T = i64> {
+    instruction_set : HashMap<i8, InstructionDef>,
+    pc : usize,
+    relative_base : i64,
+    finished : bool,
+    memory : Memory<T>,
+    input : VecDeque<T>,
+    output : Vec<T>,
+    last_output : T,
+    watchpoints : Vec<Watchpoint>,
+    watch_events : Vec<WatchEvent<T>>,
+    watch_pause : Option<WatchEvent<T>>,
+    instruction_count : u64,
+    tracer : Option<Box<dyn Tracer<T>>>,
+    trace_writes : Vec<(usize, T)>,
+    history : Option<History<T>>,
+    pending_undo : Option<UndoRecord<T>>,
+    profile : Option<Profile>,
+    coverage : Option<Coverage>,
+    decode_cache : Option<Vec<Option<Rc<Instruction<T>>>>>,
+}
+
+/// Clones the complete machine state and watchpoints. The tracer is not
+/// cloned; the copy runs untraced until it is given one.
+impl<T : Cell> Clone for Automaton<T> {
+    fn clone(&self) -> Self {
+        Automaton {
+            instruction_set: self.instruction_set.clone(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            finished: self.finished,
+            memory: self.memory.clone(),
+            input: self.input.clone(),
+            output: self.output.clone(),
+            last_output: self.last_output.clone(),
+            watchpoints: self.watchpoints.clone(),
+            watch_events: self.watch_events.clone(),
+            watch_pause: None,
+            instruction_count: self.instruction_count,
+            tracer: None,
+            trace_writes: Vec::new(),
+            history: self.history.clone(),
+            pending_undo: None,
+            profile: self.profile.clone(),
+            coverage: self.coverage.clone(),
+            decode_cache: self.decode_cache.as_ref().map(|_| Vec::new()),
+        }
+    }
+}
+
+impl<T : Cell> Default for Automaton<T> {
+    fn default() -> Self {
+        Automaton::new()
+    }
+}
+
+impl<T : Cell> Automaton<T> {
+
+    /// Creates an automaton with the full instruction set and empty memory.
+    pub fn new() -> Automaton<T> {
+        let mut automaton = Automaton {
+            instruction_set: HashMap::new(),
+            pc: 0,
+            relative_base: 0,
+            finished: false,
+            memory: Memory::new(vec![]),
+            input: VecDeque::new(),
+            output: Vec::new(),
+            last_output: T::zero(),
+            watchpoints: Vec::new(),
+            watch_events: Vec::new(),
+            watch_pause: None,
+            instruction_count: 0,
+            tracer: None,
+            trace_writes: Vec::new(),
+            history: None,
+            pending_undo: None,
+            profile: None,
+            coverage: None,
+            decode_cache: None,
+        };
+        automaton.init();
+        automaton
+    }
+
+    pub fn init(&mut self) -> &mut Self {
+        for def in INSTRUCTION_SET.iter() {
+            self.instruction_set.insert(def.opcode, *def);
+        }
+
+        self
+    }
+
+    pub fn load(&mut self, input : &str) -> Result<&mut Self, ParseError> {
+        Ok(self.load_memory(parse_program(input)?))
+    }
+
+    /// Loads an already parsed program image, resetting the machine and its
+    /// input and output. A memory limit set with `set_memory_limit`, any
+    /// watchpoints and the tracer are kept.
+    pub fn load_memory(&mut self, memory : Vec<T>) -> &mut Self {
+        let limit = self.memory.get_limit();
+        self.pc = 0;
+        self.relative_base = 0;
+        self.finished = false;
+        self.input.clear();
+        self.output.clear();
+        self.watch_events.clear();
+        self.instruction_count = 0;
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        self.last_output = T::zero();
+        self.memory = Memory::new(memory);
+        self.memory.set_limit(limit);
+        self.clear_decode_cache();
+
+        self
+    }
+
+    /// Captures memory, registers, pending input and buffered output.
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot {
+            memory : self.memory.clone(),
+            pc : self.pc,
+            relative_base : self.relative_base,
+            finished : self.finished,
+            input : self.input.clone(),
+            output : self.output.clone(),
+            last_output : self.last_output.clone(),
+            instruction_count : self.instruction_count,
+        }
+    }
+
+    /// Puts the machine back into the state captured by `snapshot`.
+    /// Watchpoints and the tracer are kept; the watch log is cleared.
+    pub fn restore(&mut self, snapshot : &Snapshot<T>) -> &mut Self {
+        self.memory = snapshot.memory.clone();
+        self.clear_decode_cache();
+        self.pc = snapshot.pc;
+        self.relative_base = snapshot.relative_base;
+        self.finished = snapshot.finished;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        self.last_output = snapshot.last_output.clone();
+        self.instruction_count = snapshot.instruction_count;
+        self.watch_events.clear();
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+
+        self
+    }
+
+    /// Limits memory to `limit` cells; touching an address beyond it fails
+    /// with `ErrorKind::MemoryLimitExceeded` instead of allocating.
+    pub fn set_memory_limit(&mut self, limit : usize) -> &mut Self {
+        self.memory.set_limit(Some(limit));
+
+        self
+    }
+
+    /// Decodes the instruction at the PC, from the decode cache if it is enabled.
+    fn decode(&mut self) -> Result<Rc<Instruction<T>>, IntcodeError> {
+        let pc = self.pc;
+        let cached = match self.decode_cache.as_mut() {
+            Some(cache) => {
+                if pc >= cache.len() && pc < self.memory.as_slice().len() {
+                    cache.resize(self.memory.as_slice().len(), None);
+                }
+                cache.get(pc).cloned()
+            },
+            None => None,
+        };
+        match cached {
+            Some(Some(instruction)) => Ok(instruction),
+            Some(None) => {
+                let instruction = Rc::new(self.decode_at(pc)?);
+                if let Some(cache) = self.decode_cache.as_mut() {
+                    cache[pc] = Some(instruction.clone());
+                }
+                Ok(instruction)
+            },
+            None => Ok(Rc::new(self.decode_at(pc)?)),
+        }
+    }
+
+    /// Decodes the instruction stored at `pc` without executing it.
+    pub fn decode_at(&self, pc : usize) -> Result<Instruction<T>, IntcodeError> {
+        // memory past the image reads as zero; running into it is running off the program
+        if pc >= self.memory.get_image_len() && self.memory.read(pc).is_ok_and(|raw| *raw == T::zero()) {
+            return Err(IntcodeError::new(pc, None, ErrorKind::PcOutOfRange(pc as i64)));
+        }
+        let raw = self.memory.read(pc)
+            .map_err(|_| self.error_at(pc, ErrorKind::PcOutOfRange(pc as i64)))?;
+        let val = match raw.to_i64() {
+            Some(val @ 1..=99999) => val,
+            _ => return Err(self.error_at(pc, ErrorKind::UnknownOpcode)),
+        };
+        let opcode = (val % 100) as i8;
+        let no_params = self.get_instruction_def(pc, opcode)?.no_params as usize;
+
+        let mut modes = val / 100;
+        let mut params = Vec::with_capacity(no_params);
+        for i in 1..=3 {
+            let param_mode = modes % 10;
+            modes /= 10;
+            if i > no_params {
+                // modes for parameters the instruction does not have must be left out
+                if param_mode != 0 {
+                    return Err(self.error_at(pc, ErrorKind::InvalidParameterMode(param_mode)));
+                }
+                continue;
+            }
+
+            params.push(Parameter {
+                param : self.read_param(pc, i)?,
+                mode : match param_mode {
+                    0 => ParameterMode::Position,
+                    1 => ParameterMode::Immediate,
+                    2 => ParameterMode::Relative,
+                    _ => return Err(self.error_at(pc, ErrorKind::InvalidParameterMode(param_mode))),
+                },
+            });
+        }
+        Ok(Instruction {
+            opcode,
+            params,
+        })
+    }
+
+    fn get_instruction_def(&self, pc : usize, opcode : i8) -> Result<&InstructionDef, IntcodeError> {
+        self.instruction_set.get(&opcode).ok_or_else(|| self.error_at(pc, ErrorKind::UnknownOpcode))
+    }
+
+    fn read_param(&self, pc : usize, offset : usize) -> Result<T, IntcodeError> {
+        let address = pc.checked_add(offset)
+            .ok_or_else(|| self.error_at(pc, ErrorKind::PcOutOfRange(pc as i64)))?;
+        self.memory.read(address).cloned().map_err(|kind| self.error_at(pc, kind))
+    }
+
+    /// Executes a single instruction. An input instruction with nothing in
+    /// the queue is not executed; `NeedsInput` is returned instead so the
+    /// caller can push a value and step again.
+    pub fn step(&mut self) -> Result<Status<T>, IntcodeError> {
+        if self.finished {
+            return Ok(Status::Halted);
+        }
+
+        let instruction = self.decode()?;
+        let (pc, opcode) = (self.pc, instruction.opcode);
+        if opcode == 3 && self.input.is_empty() {
+            return Ok(Status::NeedsInput);
+        }
+        self.watch_pause = None;
+        self.pending_undo = match self.history {
+            Some(_) => Some(UndoRecord {
+                pc : self.pc,
+                relative_base : self.relative_base,
+                finished : self.finished,
+                last_output : self.last_output.clone(),
+                output_len : self.output.len(),
+                input : None,
+                writes : Vec::new(),
+            }),
+            None => None,
+        };
+        if !self.watchpoints.is_empty() {
+            self.check_read_watchpoints(&instruction)?;
+        }
+        if self.coverage.is_some() {
+            self.record_coverage(&instruction)?;
+        }
+        if self.tracer.is_some() {
+            self.trace(&instruction)?;
+        } else {
+            self.do_operation(&instruction)?;
+        }
+        self.instruction_count += 1;
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(pc, opcode);
+        }
+        if let (Some(history), Some(record)) = (self.history.as_mut(), self.pending_undo.take()) {
+            history.push(record);
+        }
+
+        if let Some(event) = self.watch_pause.take() {
+            return Ok(Status::Watchpoint(event));
+        }
+        Ok(match opcode {
+            4 => Status::Output(self.last_output.clone()),
+            _ if self.finished => Status::Halted,
+            _ => Status::Running,
+        })
+    }
+
+    /// Caches decoded instructions per address so loops are decoded only
+    /// once. Writes into cached instructions, as done by self-modifying
+    /// programs, invalidate them.
+    pub fn enable_decode_cache(&mut self) -> &mut Self {
+        self.decode_cache = Some(Vec::new());
+
+        self
+    }
+
+    pub fn disable_decode_cache(&mut self) -> &mut Self {
+        self.decode_cache = None;
+
+        self
+    }
+
+    fn clear_decode_cache(&mut self) {
+        if let Some(cache) = self.decode_cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    /// Drops cached instructions that `address` is part of.
+    fn invalidate_decode_cache(&mut self, address : usize) {
+        if let Some(cache) = self.decode_cache.as_mut() {
+            let first = address.saturating_sub(MAX_INSTRUCTION_LENGTH - 1);
+            for entry in cache.iter_mut().take(address.saturating_add(1)).skip(first) {
+                *entry = None;
+            }
+        }
+    }
+
+    /// Starts counting executed instructions per address and opcode,
+    /// discarding any earlier profile.
+    pub fn enable_profiling(&mut self) -> &mut Self {
+        self.profile = Some(Profile::new());
+
+        self
+    }
+
+    pub fn get_profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Stops profiling and returns what was gathered.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// Starts recording which addresses are executed and which are used
+    /// as data, discarding any earlier coverage.
+    pub fn enable_coverage(&mut self) -> &mut Self {
+        self.coverage = Some(Coverage::new());
+
+        self
+    }
+
+    pub fn get_coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Stops recording coverage and returns what was gathered.
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Starts recording an undo log so execution can be reversed with
+    /// `step_back`, `run_back_to_write` and `rewind_to`. With a limit only
+    /// that many of the most recent instructions can be undone.
+    pub fn enable_history(&mut self, limit : Option<usize>) -> &mut Self {
+        self.history = Some(History::new(limit));
+
+        self
+    }
+
+    pub fn disable_history(&mut self) -> &mut Self {
+        self.history = None;
+
+        self
+    }
+
+    /// Number of instructions that can currently be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |history| history.len())
+    }
+
+    /// Undoes the last executed instruction: memory writes, registers,
+    /// consumed input and produced output. Returns false when there is
+    /// nothing left to undo.
+    pub fn step_back(&mut self) -> bool {
+        let record = match self.history.as_mut().and_then(|history| history.pop()) {
+            Some(record) => record,
+            None => return false,
+        };
+        for (address, old) in record.writes.into_iter().rev() {
+            // the address was written before, so it is within the limit
+            let _ = self.memory.write(address, old);
+            self.invalidate_decode_cache(address);
+        }
+        if let Some(input) = record.input {
+            self.input.push_front(input);
+        }
+        self.output.truncate(record.output_len);
+        self.last_output = record.last_output;
+        self.pc = record.pc;
+        self.relative_base = record.relative_base;
+        self.finished = record.finished;
+        self.instruction_count -= 1;
+        true
+    }
+
+    /// Runs backwards until just before the most recent instruction that
+    /// wrote `address`, which is then the instruction at the PC. Returns
+    /// false, without moving, if no recorded instruction wrote it.
+    pub fn run_back_to_write(&mut self, address : usize) -> bool {
+        let steps = match self.history.as_ref().and_then(|history| history.steps_to_write(address)) {
+            Some(steps) => steps,
+            None => return false,
+        };
+        for _ in 0..steps {
+            self.step_back();
+        }
+        true
+    }
+
+    /// Rewinds until exactly `count` instructions have been executed.
+    /// Returns false, without moving, if the undo log does not reach back that far.
+    pub fn rewind_to(&mut self, count : u64) -> bool {
+        if count > self.instruction_count || self.instruction_count - count > self.history_len() as u64 {
+            return false;
+        }
+        while self.instruction_count > count {
+            self.step_back();
+        }
+        true
+    }
+
+    /// Runs until the machine needs input, produces an output or halts.
+    /// The machine can be resumed by calling `run` again.
+    pub fn run(&mut self) -> Result<Status<T>, IntcodeError> {
+        let start = self.profile.as_ref().map(|_| Instant::now());
+        let status = loop {
+            match self.step() {
+                Ok(Status::Running) => (),
+                status => break status,
+            }
+        };
+        if let (Some(profile), Some(start)) = (self.profile.as_mut(), start) {
+            profile.add_time(start.elapsed());
+        }
+        status
+    }
+
+    /// Runs until the machine halts, collecting all output. Running out of
+    /// input is an error; watchpoints are logged but do not pause.
+    pub fn run_to_halt(&mut self) -> Result<&mut Self, IntcodeError> {
+        loop {
+            match self.run()? {
+                Status::Output(_) | Status::Watchpoint(_) | Status::Running => (),
+                Status::NeedsInput => return Err(self.error(ErrorKind::NoInput)),
+                Status::Halted => return Ok(self),
+            }
+        }
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn get_relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// Number of instructions executed since the program was loaded.
+    pub fn get_instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The contiguous part of memory: the loaded image plus any growth past it.
+    pub fn dump_memory(&self) -> &[T] {
+        self.memory.as_slice()
+    }
+
+    pub fn get_memory(&self) -> &Memory<T> {
+        &self.memory
+    }
+
+    /// Writes a memory cell from outside the program, e.g. to patch an image.
+    /// Watchpoints do not see this write.
+    pub fn write_memory(&mut self, address : usize, value : T) -> Result<(), IntcodeError> {
+        self.invalidate_decode_cache(address);
+        self.memory.write(address, value).map_err(|kind| self.error(kind))
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint : Watchpoint) -> &mut Self {
+        self.watchpoints.push(watchpoint);
+
+        self
+    }
+
+    /// Removes all watchpoints covering `address`, returning how many there were.
+    pub fn remove_watchpoints(&mut self, address : usize) -> usize {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| !watchpoint.range.contains(&address));
+        before - self.watchpoints.len()
+    }
+
+    pub fn get_watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// All watchpoint hits since the program was loaded or the log was last taken.
+    pub fn take_watch_events(&mut self) -> Vec<WatchEvent<T>> {
+        std::mem::take(&mut self.watch_events)
+    }
+
+    /// Installs a tracer that is called after every executed instruction.
+    pub fn set_tracer(&mut self, tracer : Box<dyn Tracer<T>>) -> &mut Self {
+        self.tracer = Some(tracer);
+
+        self
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer<T>>> {
+        self.tracer.take()
+    }
+
+    pub fn get_last_output(&self) -> T {
+        self.last_output.clone()
+    }
+
+    /// All values output since the program was loaded or the buffer was last taken.
+    pub fn get_output(&self) -> &[T] {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Replaces any pending input with the single value `input`.
+    pub fn set_input(&mut self, input : T) -> &mut Self {
+        self.input.clear();
+        self.input.push_back(input);
+
+        self
+    }
+
+    /// Input that has been pushed but not read yet.
+    pub fn get_input(&self) -> &VecDeque<T> {
+        &self.input
+    }
+
+    /// Appends `input` to the queue read by opcode 3.
+    pub fn push_input(&mut self, input : T) -> &mut Self {
+        self.input.push_back(input);
+
+        self
+    }
+
+    pub fn push_inputs<I : IntoIterator<Item = T>>(&mut self, inputs : I) -> &mut Self {
+        self.input.extend(inputs);
+
+        self
+    }
+
+    fn read_input(&mut self) -> Result<T, IntcodeError> {
+        let value = self.input.pop_front();
+        if let (Some(record), Some(value)) = (self.pending_undo.as_mut(), value.as_ref()) {
+            record.input = Some(value.clone());
+        }
+        value.ok_or_else(|| self.error(ErrorKind::NoInput))
+    }
+
+    fn do_operation(&mut self, instruction : &Instruction<T>) -> Result<&Self, IntcodeError> {
+        let mut jump = None;
+        match instruction.opcode {
+            1 => self.op_add(instruction)?,
+            2 => self.op_mult(instruction)?,
+            3 => self.op_input(instruction)?,
+            4 => self.op_output(instruction)?,
+
+            5 => jump = self.op_jump_if_true(instruction)?,
+            6 => jump = self.op_jump_if_false(instruction)?,
+            7 => self.op_less_than(instruction)?,
+            8 => self.op_equals(instruction)?,
+            9 => self.op_adjust_relative_base(instruction)?,
+
+            99 => self.op_exit(),
+            _ => return Err(self.error(ErrorKind::UnknownOpcode)),
+        }
+
+        if !self.finished {
+            self.pc = match jump {
+                Some(address) => address,
+                None => self.pc + self.get_increment_for_opcode(instruction.opcode)?,
+            };
+        }
+
+        Ok(self)
+    }
+
+    /// Builds an error for the instruction at the current PC.
+    pub(crate) fn error(&self, kind : ErrorKind) -> IntcodeError {
+        self.error_at(self.pc, kind)
+    }
+
+    fn error_at(&self, pc : usize, kind : ErrorKind) -> IntcodeError {
+        let instruction = self.memory.read(pc).ok().and_then(|raw| raw.to_i64());
+        IntcodeError::new(pc, instruction, kind)
+    }
+
+    fn get_param_value<'a>(&'a self, instr : &'a Instruction<T>, param_index: usize) -> Result<&'a T, IntcodeError> {
+        let param = &instr.params[param_index];
+        match param.mode {
+            ParameterMode::Immediate => Ok(&param.param),
+            _ => self.memory.read(self.get_address_value(instr, param_index)?)
+                .map_err(|kind| self.error(kind)),
+        }
+    }
+
+    /// Resolves the address a parameter refers to, honouring relative mode.
+    /// Parameters that are written to must not be in immediate mode.
+    fn get_address_value(&self, instr : &Instruction<T>, param_index: usize) -> Result<usize, IntcodeError> {
+        let param = &instr.params[param_index];
+        let address = match param.mode {
+            ParameterMode::Position => param.param.to_i64(),
+            ParameterMode::Relative => param.param.to_i64()
+                .and_then(|offset| self.relative_base.checked_add(offset)),
+            ParameterMode::Immediate => return Err(self.error(ErrorKind::WriteInImmediateMode)),
+        };
+        match address {
+            Some(address) if address >= 0 => Ok(address as usize),
+            Some(address) => Err(self.error(ErrorKind::NegativeAddress(address))),
+            None => Err(self.error(ErrorKind::Overflow)),
+        }
+    }
+
+    pub(crate) fn get_jump_target(&self, target : &T) -> Result<usize, IntcodeError> {
+        match target.to_i64() {
+            Some(address) if address >= 0 => Ok(address as usize),
+            Some(address) => Err(self.error(ErrorKind::PcOutOfRange(address))),
+            None => Err(self.error(ErrorKind::Overflow)),
+        }
+    }
+
+    pub(crate) fn write(&mut self, address : usize, value : T) -> Result<(), IntcodeError> {
+        if self.tracer.is_some() {
+            self.trace_writes.push((address, value.clone()));
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_data(address);
+        }
+        self.invalidate_decode_cache(address);
+        if self.pending_undo.is_some() {
+            let old = self.memory.read(address).map_err(|kind| self.error(kind))?.clone();
+            if let Some(record) = self.pending_undo.as_mut() {
+                record.writes.push((address, old));
+            }
+        }
+        if self.watchpoints.iter().any(|watchpoint| watchpoint.matches(address, true)) {
+            let old = self.memory.read(address).map_err(|kind| self.error(kind))?.clone();
+            self.record_watch_event(address, old, Some(value.clone()), true);
+        }
+        self.memory.write(address, value).map_err(|kind| self.error(kind))
+    }
+
+    /// Executes `instruction` and reports it to the tracer.
+    fn trace(&mut self, instruction : &Instruction<T>) -> Result<(), IntcodeError> {
+        let write_param = self.get_instruction_def(self.pc, instruction.opcode)?.write_param;
+        let mut operands = Vec::with_capacity(instruction.params.len());
+        for index in 0..instruction.params.len() {
+            let operand = if write_param == Some(index) {
+                let address = self.get_address_value(instruction, index)?;
+                T::from_i64(address as i64).ok_or_else(|| self.error(ErrorKind::Overflow))?
+            } else {
+                self.get_param_value(instruction, index)?.clone()
+            };
+            operands.push(operand);
+        }
+        let (pc, relative_base) = (self.pc, self.relative_base);
+
+        self.trace_writes.clear();
+        self.do_operation(instruction)?;
+
+        let event = TraceEvent {
+            count : self.instruction_count,
+            pc,
+            relative_base,
+            instruction : instruction.clone(),
+            operands,
+            writes : std::mem::take(&mut self.trace_writes),
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.trace(&event);
+        }
+        Ok(())
+    }
+
+    /// The memory addresses the operands of `instruction` read from.
+    fn read_addresses(&self, instruction : &Instruction<T>) -> Result<Vec<usize>, IntcodeError> {
+        let write_param = self.get_instruction_def(self.pc, instruction.opcode)?.write_param;
+        let mut addresses = Vec::new();
+        for (index, param) in instruction.params.iter().enumerate() {
+            if param.mode != ParameterMode::Immediate && write_param != Some(index) {
+                addresses.push(self.get_address_value(instruction, index)?);
+            }
+        }
+        Ok(addresses)
+    }
+
+    fn record_coverage(&mut self, instruction : &Instruction<T>) -> Result<(), IntcodeError> {
+        let addresses = self.read_addresses(instruction)?;
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record_instruction(self.pc, instruction.params.len() + 1);
+            for address in addresses {
+                coverage.record_data(address);
+            }
+        }
+        Ok(())
+    }
+
+    /// Records reads of watched addresses by the operands of `instruction`.
+    fn check_read_watchpoints(&mut self, instruction : &Instruction<T>) -> Result<(), IntcodeError> {
+        for address in self.read_addresses(instruction)? {
+            if self.watchpoints.iter().any(|watchpoint| watchpoint.matches(address, false)) {
+                let value = self.memory.read(address).map_err(|kind| self.error(kind))?.clone();
+                self.record_watch_event(address, value, None, false);
+            }
+        }
+        Ok(())
+    }
+
+    fn record_watch_event(&mut self, address : usize, old : T, new : Option<T>, write : bool) {
+        let event = WatchEvent { address, pc : self.pc, old, new };
+        let pause = self.watchpoints.iter()
+            .any(|watchpoint| watchpoint.pause && watchpoint.matches(address, write));
+        if pause {
+            self.watch_pause = Some(event.clone());
+        }
+        self.watch_events.push(event);
+    }
+
+    fn get_increment_for_opcode(&self, opcode : i8) -> Result<usize, IntcodeError> {
+        Ok((self.get_instruction_def(self.pc, opcode)?.no_params + 1) as usize)
+    }
+
+    fn op_add(&mut self, instr : &Instruction<T>) -> Result<(), IntcodeError> {
+        let op1 = self.get_param_value(instr, 0)?;
+        let op2 = self.get_param_value(instr, 1)?;
+        let address = self.get_address_value(instr, 2)?;
+        let result = op1.checked_add(op2)
+            .ok_or_else(|| self.error(ErrorKind::Overflow))?;
+        self.write(address, result)
+    }
+
+    fn op_mult(&mut self, instr : &Instruction<T>) -> Result<(), IntcodeError> {
+        let op1 = self.get_param_value(instr, 0)?;
+        let op2 = self.get_param_value(instr, 1)?;
+        let address = self.get_address_value(instr, 2)?;
+        let result = op1.checked_mul(op2)
+            .ok_or_else(|| self.error(ErrorKind::Overflow))?;
+        self.write(address, result)
+    }
+
+    fn op_jump_if_true(&self, instr : &Instruction<T>) -> Result<Option<usize>, IntcodeError> {
+        let op1 = self.get_param_value(instr, 0)?;
+        let op2 = self.get_param_value(instr, 1)?;
+
+        if *op1 != T::zero() {
+            Ok(Some(self.get_jump_target(op2)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn op_jump_if_false(&self, instr : &Instruction<T>) -> Result<Option<usize>, IntcodeError> {
+        let op1 = self.get_param_value(instr, 0)?;
+        let op2 = self.get_param_value(instr, 1)?;
+
+        if *op1 == T::zero() {
+            Ok(Some(self.get_jump_target(op2)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn op_less_than(&mut self, instr : &Instruction<T>) -> Result<(), IntcodeError> {
+        let op1 = self.get_param_value(instr, 0)?;
+        let op2 = self.get_param_value(instr, 1)?;
+        let address = self.get_address_value(instr, 2)?;
+
+        let result = if *op1 < *op2 { T::one() } else { T::zero() };
+        self.write(address, result)
+    }
+
+    fn op_equals(&mut self, instr : &Instruction<T>) -> Result<(), IntcodeError> {
+        let op1 = self.get_param_value(instr, 0)?;
+        let op2 = self.get_param_value(instr, 1)?;
+        let address = self.get_address_value(instr, 2)?;
+
+        let result = if *op1 == *op2 { T::one() } else { T::zero() };
+        self.write(address, result)
+    }
+
+    fn op_input(&mut self, instr : &Instruction<T>) -> Result<(), IntcodeError> {
+        let address = self.get_address_value(instr, 0)?;
+        // check the address first so a failing write leaves the input queued
+        self.memory.read(address).map_err(|kind| self.error(kind))?;
+        let value = self.read_input()?;
+        self.write(address, value)
+    }
+
+    fn op_output(&mut self, instr : &Instruction<T>) -> Result<(), IntcodeError> {
+        let value = self.get_param_value(instr, 0)?.clone();
+        self.emit(value);
+        Ok(())
+    }
+
+    pub(crate) fn emit(&mut self, value : T) {
+        self.output.push(value.clone());
+        self.last_output = value;
+    }
+
+    fn op_adjust_relative_base(&mut self, instr : &Instruction<T>) -> Result<(), IntcodeError> {
+        let offset = self.get_param_value(instr, 0)?.clone();
+        self.adjust_relative_base(&offset)
+    }
+
+    pub(crate) fn adjust_relative_base(&mut self, offset : &T) -> Result<(), IntcodeError> {
+        self.relative_base = offset.to_i64().and_then(|offset| self.relative_base.checked_add(offset))
+            .ok_or_else(|| self.error(ErrorKind::Overflow))?;
+        Ok(())
+    }
+
+    fn op_exit(&mut self) {
+        self.finished = true;
+    }
+
+    // Entry points for the compiled backend, which executes instructions
+    // without going through `step`.
+
+    pub(crate) fn set_pc(&mut self, pc : usize) {
+        self.pc = pc;
+    }
+
+    pub(crate) fn halt(&mut self) {
+        self.finished = true;
+    }
+
+    pub(crate) fn count_instruction(&mut self) {
+        self.instruction_count += 1;
+    }
+
+    /// Whether any instrumentation that only `step` drives is enabled.
+    pub(crate) fn has_hooks(&self) -> bool {
+        !self.watchpoints.is_empty() || self.tracer.is_some() || self.history.is_some()
+            || self.profile.is_some() || self.coverage.is_some()
+    }
+
+    pub(crate) fn read_cell(&self, address : usize) -> Result<T, IntcodeError> {
+        self.memory.read(address).cloned().map_err(|kind| self.error(kind))
+    }
+
+    pub(crate) fn relative_address(&self, offset : i64) -> Result<usize, IntcodeError> {
+        match self.relative_base.checked_add(offset) {
+            Some(address) if address >= 0 => Ok(address as usize),
+            Some(address) => Err(self.error(ErrorKind::NegativeAddress(address))),
+            None => Err(self.error(ErrorKind::Overflow)),
+        }
+    }
+
+    /// The address `instruction` at the PC would write to, if any.
+    pub(crate) fn write_target(&self, instruction : &Instruction<T>) -> Option<usize> {
+        let index = self.instruction_set.get(&instruction.opcode)?.write_param?;
+        self.get_address_value(instruction, index).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_program, Automaton, ErrorKind, IntcodeError, ParseError, Status, WatchEvent, WatchKind, Watchpoint};
+
+    #[test]
+    fn test_one() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1,0,0,0,99").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(*automaton.dump_memory(), vec![2,0,0,0,99]);
+    }
+
+    #[test]
+    fn test_two() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("2,3,0,3,99").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(*automaton.dump_memory(), vec![2,3,0,6,99]);
+    }
+
+    #[test]
+    fn test_three() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("2,4,4,5,99,0").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(*automaton.dump_memory(), vec![2,4,4,5,99,9801]);
+    }
+
+    #[test]
+    fn test_four() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1,1,1,4,99,5,6,0,99").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(*automaton.dump_memory(), vec![30,1,1,4,2,5,6,0,99]);
+    }
+
+    #[test]
+    fn test_five() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1002,4,3,4,33").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(*automaton.dump_memory(), vec![1002,4,3,4,99]);
+    }
+
+    #[test]
+    fn test_negative_values() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1101,100,-1,4,0").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(*automaton.dump_memory(), vec![1101,100,-1,4,99]);
+    }
+
+    #[test]
+    fn test_load_memory() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load_memory(vec![1,9,10,3,2,3,11,0,99,30,40,50]).run_to_halt().unwrap();
+        assert_eq!(automaton.dump_memory()[0], 3500);
+        assert!(automaton.is_finished());
+    }
+
+    #[test]
+    fn test_equal_positional() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,9,8,9,10,9,4,9,99,-1,8").unwrap().set_input(8).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1);
+
+        automaton.load("3,9,8,9,10,9,4,9,99,-1,8").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 0);
+    }
+
+    #[test]
+    fn test_less_than_positional() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,9,7,9,10,9,4,9,99,-1,8").unwrap().set_input(7).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1);
+
+        automaton.load("3,9,7,9,10,9,4,9,99,-1,8").unwrap().set_input(9).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 0);
+    }
+
+    #[test]
+    fn test_equal_immediate() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,3,1108,-1,8,3,4,3,99").unwrap().set_input(8).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1);
+
+        automaton.load("3,3,1108,-1,8,3,4,3,99").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 0);
+    }
+
+    #[test]
+    fn test_less_than_immediate() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,3,1107,-1,8,3,4,3,99").unwrap().set_input(7).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1);
+
+        automaton.load("3,3,1107,-1,8,3,4,3,99").unwrap().set_input(9).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 0);
+    }
+
+    #[test]
+    fn test_jump_position() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9").unwrap().set_input(0).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 0);
+
+        automaton.load("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1);
+    }
+
+    #[test]
+    fn test_jump_immediate() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap().set_input(0).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 0);
+
+        automaton.load("3,3,1105,-1,9,1101,0,0,12,4,12,99,1").unwrap().set_input(1).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1);
+    }
+
+    #[test]
+    fn test_around_eight() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton
+            .load("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap()
+            .set_input(7).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 999);
+
+        automaton
+            .load("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap()
+            .set_input(8).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1000);
+
+        automaton
+            .load("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap()
+            .set_input(9).run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1001);
+    }
+
+    #[test]
+    fn test_relative_read() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("109,1,204,-1,99").unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 109);
+        assert_eq!(automaton.get_relative_base(), 1);
+    }
+
+    #[test]
+    fn test_relative_write() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("109,7,203,1,204,1,99,0,0").unwrap().set_input(42).run_to_halt().unwrap();
+        assert_eq!(automaton.dump_memory()[8], 42);
+        assert_eq!(automaton.get_last_output(), 42);
+    }
+
+    #[test]
+    fn test_relative_arithmetic() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("109,11,22201,0,1,2,209,2,204,-5,99,3,4,0").unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.dump_memory()[13], 7);
+        assert_eq!(automaton.get_relative_base(), 18);
+        assert_eq!(automaton.get_last_output(), 7);
+    }
+
+    #[test]
+    fn test_input_queue_and_output_buffer() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,0,3,1,1,0,1,2,4,2,4,0,99").unwrap().push_inputs(vec![3, 4]).run_to_halt().unwrap();
+        assert_eq!(automaton.get_output(), &[7, 3]);
+        assert_eq!(automaton.get_last_output(), 3);
+
+        assert_eq!(automaton.take_output(), vec![7, 3]);
+        assert!(automaton.get_output().is_empty());
+    }
+
+    #[test]
+    fn test_missing_input() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("3,0,3,1,99").unwrap().push_input(3).run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(2, Some(3), ErrorKind::NoInput));
+    }
+
+    #[test]
+    fn test_step() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1101,2,3,0,4,0,99").unwrap();
+        assert_eq!(automaton.step().unwrap(), Status::Running);
+        assert_eq!(automaton.step().unwrap(), Status::Output(5));
+        assert_eq!(automaton.step().unwrap(), Status::Halted);
+        assert_eq!(automaton.step().unwrap(), Status::Halted);
+    }
+
+    #[test]
+    fn test_run_pauses_for_input_and_output() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,7,4,7,1105,1,0,0").unwrap();
+        assert_eq!(automaton.run().unwrap(), Status::NeedsInput);
+        assert_eq!(automaton.run().unwrap(), Status::NeedsInput);
+
+        automaton.push_input(5);
+        assert_eq!(automaton.run().unwrap(), Status::Output(5));
+        assert_eq!(automaton.run().unwrap(), Status::NeedsInput);
+
+        automaton.push_inputs(vec![6, 7]);
+        assert_eq!(automaton.run().unwrap(), Status::Output(6));
+        assert_eq!(automaton.run().unwrap(), Status::Output(7));
+        assert_eq!(automaton.run().unwrap(), Status::NeedsInput);
+        assert_eq!(automaton.get_output(), &[5, 6, 7]);
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("1101,1,1,5,98,0").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(4, Some(98), ErrorKind::UnknownOpcode));
+
+        let error = automaton.load("1101,1,1,5,0").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(4, Some(0), ErrorKind::UnknownOpcode));
+
+        let error = automaton.load("-1").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(0, Some(-1), ErrorKind::UnknownOpcode));
+    }
+
+    #[test]
+    fn test_pc_past_image() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("1101,1,1,5").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(4, None, ErrorKind::PcOutOfRange(4)));
+
+        // code the program writes past its image runs
+        automaton.load("1101,0,99,4").unwrap().run_to_halt().unwrap();
+        assert!(automaton.is_finished());
+    }
+
+    #[test]
+    fn test_invalid_parameter_mode() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("301,0,0,0,99").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(0, Some(301), ErrorKind::InvalidParameterMode(3)));
+
+        let error = automaton.load("1104,0,99").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error.kind, ErrorKind::InvalidParameterMode(1));
+    }
+
+    #[test]
+    fn test_write_in_immediate_mode() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("11101,1,1,0,99").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(0, Some(11101), ErrorKind::WriteInImmediateMode));
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("1,-1,0,0,99").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(0, Some(1), ErrorKind::NegativeAddress(-1)));
+
+        let error = automaton.load("109,-5,203,2,99").unwrap().set_input(1).run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(2, Some(203), ErrorKind::NegativeAddress(-3)));
+    }
+
+    #[test]
+    fn test_pc_out_of_range() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("1105,1,-5").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(0, Some(1105), ErrorKind::PcOutOfRange(-5)));
+
+        let error = automaton.set_memory_limit(10).load("1105,1,20").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error, IntcodeError::new(20, None, ErrorKind::PcOutOfRange(20)));
+    }
+
+    #[test]
+    fn test_decode_at() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1002,4,3,4,33,109,-2,21101,1,2,3,99").unwrap();
+        assert_eq!(automaton.decode_at(0).unwrap().to_string(), "mul [4], #3, [4]");
+        assert_eq!(automaton.decode_at(5).unwrap().to_string(), "arb #-2");
+        assert_eq!(automaton.decode_at(7).unwrap().to_string(), "add #1, #2, rb+3");
+        assert_eq!(automaton.decode_at(11).unwrap().to_string(), "hlt");
+        assert_eq!(automaton.decode_at(4).err().unwrap().kind, ErrorKind::UnknownOpcode);
+        assert_eq!(automaton.get_pc(), 0);
+    }
+
+    #[test]
+    fn test_write_watchpoint() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.add_watchpoint(Watchpoint::new(4..5, WatchKind::Write)).load("1002,4,3,4,33").unwrap();
+        let event = WatchEvent { address : 4, pc : 0, old : 33, new : Some(99) };
+        assert_eq!(automaton.step().unwrap(), Status::Watchpoint(event.clone()));
+        assert_eq!(automaton.step().unwrap(), Status::Halted);
+        assert_eq!(automaton.take_watch_events(), vec![event.clone()]);
+        assert_eq!(event.to_string(), "write [4] 33 -> 99 at pc 0");
+    }
+
+    #[test]
+    fn test_read_watchpoint() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.add_watchpoint(Watchpoint::new(9..11, WatchKind::Read)).load("1,9,10,9,4,9,99,0,0,3,4").unwrap();
+        let event = WatchEvent { address : 10, pc : 0, old : 4, new : None };
+        assert_eq!(automaton.run().unwrap(), Status::Watchpoint(event.clone()));
+        assert_eq!(automaton.run().unwrap(), Status::Watchpoint(WatchEvent { address : 9, pc : 4, old : 7, new : None }));
+        assert_eq!(automaton.run().unwrap(), Status::Halted);
+        // the write to 9 is not watched, the read of both operands is
+        assert_eq!(automaton.take_watch_events().len(), 3);
+        assert_eq!(event.to_string(), "read [10] = 4 at pc 0");
+    }
+
+    #[test]
+    fn test_logging_watchpoint() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.add_watchpoint(Watchpoint::logging(0..100, WatchKind::Access))
+            .load("3,9,8,9,10,9,4,9,99,-1,8").unwrap().set_input(8);
+        automaton.run_to_halt().unwrap();
+        let events = automaton.take_watch_events();
+        let writes : Vec<(usize, Option<i64>)> = events.iter()
+            .filter(|event| event.new.is_some())
+            .map(|event| (event.pc, event.new))
+            .collect();
+        assert_eq!(writes, vec![(0, Some(8)), (2, Some(1))]);
+        assert_eq!(events.len(), 5);
+
+        assert_eq!(automaton.remove_watchpoints(50), 1);
+        assert!(automaton.get_watchpoints().is_empty());
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_history(None).load("3,9,8,9,10,9,4,9,99,-1,8").unwrap().set_input(8);
+        automaton.run_to_halt().unwrap();
+        assert_eq!(automaton.get_output(), &[1]);
+        assert_eq!(automaton.history_len(), 4);
+
+        assert!(automaton.step_back());
+        assert!(!automaton.is_finished());
+        assert_eq!(automaton.get_pc(), 8);
+        assert!(automaton.step_back());
+        assert!(automaton.get_output().is_empty());
+        while automaton.step_back() {}
+        assert_eq!(automaton.get_pc(), 0);
+        assert_eq!(automaton.get_instruction_count(), 0);
+        assert_eq!(automaton.dump_memory(), &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        assert_eq!(automaton.get_input().iter().collect::<Vec<&i64>>(), vec![&8]);
+
+        automaton.run_to_halt().unwrap();
+        assert_eq!(automaton.get_output(), &[1]);
+    }
+
+    #[test]
+    fn test_run_back_to_write() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_history(None).load("1,0,0,9,1002,9,3,9,99,0").unwrap();
+        automaton.run_to_halt().unwrap();
+        assert_eq!(automaton.dump_memory()[9], 6);
+        assert!(automaton.run_back_to_write(9));
+        assert_eq!((automaton.get_pc(), automaton.dump_memory()[9]), (4, 2));
+        assert!(automaton.run_back_to_write(9));
+        assert_eq!((automaton.get_pc(), automaton.dump_memory()[9]), (0, 0));
+        assert!(!automaton.run_back_to_write(9));
+        assert!(!automaton.run_back_to_write(3));
+    }
+
+    #[test]
+    fn test_rewind_to() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_history(Some(2)).load("1101,1,1,9,1101,2,2,9,99,0").unwrap();
+        automaton.run_to_halt().unwrap();
+        assert_eq!(automaton.get_instruction_count(), 3);
+        assert!(!automaton.rewind_to(0));
+        assert!(!automaton.rewind_to(4));
+        assert!(automaton.rewind_to(1));
+        assert_eq!((automaton.get_pc(), automaton.dump_memory()[9]), (4, 2));
+    }
+
+    #[test]
+    fn test_decode_cache_invalidation() {
+        // rewrites the operand of an `out` that has already been executed
+        let program = "4,20,1001,1,1,1,1001,22,-1,22,1005,22,0,99,0,0,0,0,0,0,7,8,2";
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_decode_cache().load(program).unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.get_output(), &[7, 8]);
+
+        automaton.enable_history(None).load(program).unwrap().run_to_halt().unwrap();
+        automaton.rewind_to(1);
+        automaton.run_to_halt().unwrap();
+        assert_eq!(automaton.get_output(), &[7, 8]);
+
+        automaton.load("104,1,1105,1,0").unwrap();
+        assert_eq!(automaton.step().unwrap(), Status::Output(1));
+        automaton.step().unwrap();
+        automaton.write_memory(1, 5).unwrap();
+        assert_eq!(automaton.step().unwrap(), Status::Output(5));
+
+        automaton.set_memory_limit(100);
+        let error = automaton.write_memory(usize::MAX, 1).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::MemoryLimitExceeded(usize::MAX));
+    }
+
+    #[test]
+    fn test_decode_cache_matches_plain() {
+        for program in &["3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", "3,3,1105,-1,9,1101,0,0,12,4,12,99,1"] {
+            for input in 0..3 {
+                let mut plain : Automaton = Automaton::new();
+                plain.load(program).unwrap().set_input(input).run_to_halt().unwrap();
+                let mut cached : Automaton = Automaton::new();
+                cached.enable_decode_cache().load(program).unwrap().set_input(input).run_to_halt().unwrap();
+                assert_eq!(plain.get_output(), cached.get_output());
+                assert_eq!(plain.dump_memory(), cached.dump_memory());
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_past_image() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1101,2,3,10,4,10,4,2000,99").unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.dump_memory().len(), 11);
+        assert_eq!(automaton.dump_memory()[10], 5);
+        assert_eq!(automaton.get_last_output(), 0);
+    }
+
+    #[test]
+    fn test_sparse_high_address() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1101,2,3,100000000,4,100000000,99").unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 5);
+        assert_eq!(*automaton.get_memory().read(100_000_000).unwrap(), 5);
+        assert_eq!(automaton.dump_memory().len(), 7);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.set_memory_limit(100).load("1101,2,3,1000,99").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error.kind, ErrorKind::MemoryLimitExceeded(1000));
+        assert_eq!(error.pc, 0);
+
+        automaton.set_memory_limit(10).load("3,50,99").unwrap().set_input(7);
+        let error = automaton.run_to_halt().err().unwrap();
+        assert_eq!(error.kind, ErrorKind::MemoryLimitExceeded(50));
+        assert_eq!(automaton.get_input().iter().collect::<Vec<_>>(), vec![&7]);
+    }
+
+    #[test]
+    fn test_quine() {
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load(program).unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 99);
+        assert_eq!(automaton.dump_memory()[100], 16);
+    }
+
+    #[test]
+    fn test_large_values() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("1102,34915192,34915192,7,4,7,99,0").unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1219070632396864);
+
+        automaton.load("104,1125899906842624,99").unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output(), 1125899906842624);
+    }
+
+    #[test]
+    fn test_overflow_is_an_error() {
+        let mut automaton : Automaton = Automaton::new();
+        let error = automaton.load("1102,9223372036854775807,2,0,99").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error.kind, ErrorKind::Overflow);
+        assert_eq!(error.pc, 0);
+    }
+
+    #[test]
+    fn test_i32_cells() {
+        let mut automaton : Automaton<i32> = Automaton::new();
+        automaton.load("1,0,0,0,99").unwrap().run_to_halt().unwrap();
+        assert_eq!(*automaton.dump_memory(), vec![2,0,0,0,99]);
+
+        let error = automaton.load("1102,34915192,34915192,7,4,7,99,0").unwrap().run_to_halt().err().unwrap();
+        assert_eq!(error.kind, ErrorKind::Overflow);
+
+        // a cell that does not fit is reported rather than left out
+        let error = automaton.load("1101,1,1,4294967296,99").err().unwrap();
+        assert_eq!(error, ParseError { index : 3, token : String::from("4294967296") });
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_program::<i64>("1, x ,99"), Err(ParseError { index : 1, token : String::from("x") }));
+        assert_eq!(parse_program::<i64>("1,,99").err().unwrap().index, 1);
+        assert_eq!(parse_program::<i64>(" \n"), Ok(vec![]));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_cells() {
+        use num_bigint::BigInt;
+
+        let mut automaton : Automaton<BigInt> = Automaton::new();
+        automaton.load("1102,9223372036854775807,2,7,4,7,99,0").unwrap().run_to_halt().unwrap();
+        assert_eq!(automaton.get_last_output().to_string(), "18446744073709551614");
+    }
+
+}
@@ -0,0 +1,128 @@
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use intcode::{get_instruction_def, Automaton, CompiledAutomaton, Instruction, Parameter, ParameterMode, Status};
+
+/// Sums the numbers n..1 in a loop, exercising arithmetic, compares,
+/// jumps and relative-mode reads on every iteration.
+const PROGRAM : &str = "
+        arb #stack
+loop:   add [acc], [n], [acc]
+        mul [n], #1, [scratch]
+        add rb+0, [scratch], [scratch]
+        lt [scratch], #0, [flag]
+        jt [flag], #fail
+        add [n], #-1, [n]
+        jt [n], #loop
+        out [acc]
+        hlt
+fail:   out #-1
+        hlt
+n:      db 0
+acc:    db 0
+scratch: db 0
+flag:   db 0
+stack:  db 0
+";
+
+fn time(automaton : &mut Automaton, image : &[i64]) -> (Duration, i64) {
+    let start = Instant::now();
+    automaton.load_memory(image.to_vec()).run_to_halt().expect("Program failed");
+    (start.elapsed(), automaton.get_last_output())
+}
+
+/// The decoder `Automaton` used before opcodes were decoded arithmetically:
+/// the parameter modes are read from the digits of the opcode's decimal
+/// string, zero-padded to five characters. Kept here as the baseline.
+fn decode_string(image : &[i64], pc : usize) -> Option<Instruction> {
+    let val = image[pc];
+    let opcode = (val % 100) as i8;
+    let no_params = get_instruction_def(opcode)?.no_params as usize;
+    if val < 100 {
+        let params = (1..=no_params).map(|i| Parameter { param : image[pc + i], mode : ParameterMode::Position }).collect();
+        return Some(Instruction { opcode, params });
+    }
+
+    let mut op_extended = val.to_string().chars().rev().collect::<String>();
+    while op_extended.len() < 5 {
+        op_extended.push('0');
+    }
+    let op_extended = op_extended.chars().rev().collect::<String>();
+    let mut params = Vec::new();
+    for i in 1..=no_params {
+        let mode = match op_extended.as_bytes()[3 - i] - b'0' {
+            0 => ParameterMode::Position,
+            1 => ParameterMode::Immediate,
+            2 => ParameterMode::Relative,
+            _ => return None,
+        };
+        params.push(Parameter { param : image[pc + i], mode });
+    }
+    Some(Instruction { opcode, params })
+}
+
+/// The address of every instruction the program executes, in order.
+fn executed_pcs(image : &[i64]) -> Vec<usize> {
+    let mut automaton : Automaton = Automaton::new();
+    automaton.load_memory(image.to_vec());
+    let mut pcs = Vec::new();
+    while !automaton.is_finished() {
+        pcs.push(automaton.get_pc());
+        if let Status::NeedsInput = automaton.step().expect("Program failed") {
+            panic!("Benchmark program reads input");
+        }
+    }
+    pcs
+}
+
+/// Decodes every executed instruction again with `decode`, leaving out the
+/// rest of the work the interpreter does.
+fn time_decoding<F : Fn(usize) -> Option<Instruction>>(pcs : &[usize], decode : F) -> Duration {
+    let start = Instant::now();
+    for pc in pcs {
+        black_box(decode(*pc).expect("Instruction does not decode"));
+    }
+    start.elapsed()
+}
+
+/// Usage: bench [iterations]
+/// Times decoding the executed instructions with the old string-based and
+/// the arithmetic decoder, then running the benchmark program with and
+/// without the decode cache, and with the compiled backend.
+fn main() {
+    let iterations : i64 = env::args().nth(1).map_or(1_000_000, |arg| arg.parse().expect("Iterations must be a number"));
+
+    let mut image = intcode::assemble_cells(PROGRAM).expect("Benchmark program does not assemble");
+    let n = image.len() - 5;
+    image[n] = iterations;
+
+    let pcs = executed_pcs(&image);
+    let mut decoder : Automaton = Automaton::new();
+    decoder.load_memory(image.clone());
+    let string_time = time_decoding(&pcs, |pc| decode_string(&image, pc));
+    let arithmetic_time = time_decoding(&pcs, |pc| decoder.decode_at(pc).ok());
+    println!("decoding {} instructions: string {:.3?}, arithmetic {:.3?}, speedup {:.2}x",
+             pcs.len(), string_time, arithmetic_time, string_time.as_secs_f64() / arithmetic_time.as_secs_f64());
+    drop(pcs);
+
+    let mut plain : Automaton = Automaton::new();
+    let (plain_time, plain_sum) = time(&mut plain, &image);
+    println!("plain:  {:>10.3?}  sum {}", plain_time, plain_sum);
+
+    let mut cached : Automaton = Automaton::new();
+    cached.enable_decode_cache();
+    let (cached_time, cached_sum) = time(&mut cached, &image);
+    println!("cached: {:>10.3?}  sum {}", cached_time, cached_sum);
+
+    let mut compiled : CompiledAutomaton = CompiledAutomaton::new();
+    let start = Instant::now();
+    compiled.load_memory(image).run_to_halt().expect("Program failed");
+    let compiled_time = start.elapsed();
+    println!("compiled: {:>8.3?}  sum {}", compiled_time, compiled.get_last_output());
+
+    assert_eq!(plain_sum, cached_sum);
+    assert_eq!(plain_sum, compiled.get_last_output());
+    println!("speedup cached {:.2}x, compiled {:.2}x",
+             plain_time.as_secs_f64() / cached_time.as_secs_f64(),
+             plain_time.as_secs_f64() / compiled_time.as_secs_f64());
+}
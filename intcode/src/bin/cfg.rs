@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::{env, fs};
+use intcode::{Automaton, ControlFlowGraph, TraceEvent, Tracer};
+
+/// Copies every instruction into the image the way it looked when it was
+/// first executed, so code the program writes itself shows up in the graph.
+struct Patcher {
+    image : Vec<i64>,
+    seen : HashSet<usize>,
+}
+
+impl Tracer<i64> for Patcher {
+    fn trace(&mut self, event : &TraceEvent<i64>) {
+        if !self.seen.insert(event.pc) {
+            return;
+        }
+        let instruction = &event.instruction;
+        let modes = instruction.params.iter().rev().fold(0, |modes, param| modes * 10 + param.mode as i64);
+        let mut cells = vec![i64::from(instruction.opcode) + modes * 100];
+        cells.extend(instruction.params.iter().map(|param| param.param));
+        for (offset, cell) in cells.into_iter().enumerate() {
+            if let Some(slot) = self.image.get_mut(event.pc + offset) {
+                *slot = cell;
+            }
+        }
+    }
+}
+
+/// Usage: cfg <program> [input]...
+/// Prints the program's control-flow graph in Graphviz DOT format. Given
+/// input, the program is run first and instructions it rewrote before
+/// executing them are graphed as they were executed.
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let path = &args[1];
+
+    let contents = fs::read_to_string(path).expect("Failed to read contents of file");
+    let mut image : Vec<i64> = intcode::parse_program(contents.as_str()).expect("Failed to parse program");
+
+    if args.len() > 2 {
+        let inputs = args[2..].iter().map(|arg| arg.parse().expect("Input must be a number"));
+        let patcher = Rc::new(RefCell::new(Patcher { image : image.clone(), seen : HashSet::new() }));
+        let mut automaton : Automaton = Automaton::new();
+        automaton.set_tracer(Box::new(patcher.clone())).load_memory(image.clone()).push_inputs(inputs);
+        automaton.run_to_halt().expect("Program failed");
+        image = patcher.borrow().image.clone();
+    }
+
+    print!("{}", ControlFlowGraph::new(&image).to_dot());
+}
@@ -0,0 +1,32 @@
+use std::{env, fs};
+use intcode::{Automaton, Coverage};
+
+/// Usage: coverage <program> [inputs]...
+/// Runs the program once per argument, each a comma-separated list of
+/// input values, and prints the listing annotated with the merged coverage.
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let path = &args[1];
+
+    let contents = fs::read_to_string(path).expect("Failed to read contents of file");
+    let image : Vec<i64> = intcode::parse_program(contents.as_str()).expect("Failed to parse program");
+
+    let mut runs : Vec<Vec<i64>> = args[2..].iter()
+        .map(|inputs| intcode::parse_program(inputs).expect("Inputs must be comma-separated numbers"))
+        .collect();
+    if runs.is_empty() {
+        runs.push(Vec::new());
+    }
+
+    let mut coverage = Coverage::new();
+    for inputs in runs {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_coverage().load_memory(image.clone()).push_inputs(inputs);
+        if let Err(error) = automaton.run_to_halt() {
+            eprintln!("run failed: {}", error);
+        }
+        coverage.merge(automaton.get_coverage().unwrap());
+    }
+
+    print!("{}", coverage.listing(&image));
+}
@@ -0,0 +1,16 @@
+use std::{env, fs, io};
+use intcode::{Automaton, Debugger};
+
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let path = &args[1];
+    println!("Reading input file: {}", path);
+
+    let contents = fs::read_to_string(path).expect("Failed to read contents of file");
+
+    let mut automaton : Automaton = Automaton::new();
+    automaton.load(contents.as_str()).expect("Failed to parse program");
+
+    let stdin = io::stdin();
+    Debugger::new(automaton).repl(stdin.lock(), io::stdout()).expect("Failed to talk to the terminal");
+}
@@ -0,0 +1,11 @@
+use std::{env, fs};
+
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let path = &args[1];
+
+    let contents = fs::read_to_string(path).expect("Failed to read contents of file");
+    let image : Vec<i64> = intcode::parse_program(contents.as_str()).expect("Failed to parse program");
+
+    print!("{}", intcode::listing(&image));
+}
@@ -0,0 +1,23 @@
+use std::{env, fs};
+use intcode::{parse_program, Automaton, HullRobot};
+
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let path = &args[1];
+    println!("Reading input file: {}", path);
+
+    let contents = fs::read_to_string(path).expect("Failed to read contents of file");
+    let program : Vec<i64> = parse_program(contents.as_str()).expect("Failed to parse program");
+
+    for start_white in [false, true].iter() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load_memory(program.clone());
+        let mut robot = HullRobot::new(automaton);
+        robot.paint((0,0), *start_white);
+        match robot.run() {
+            Ok(robot) => println!("Starting on a {} panel, painted {} panels:\n{}",
+                                  if *start_white { "white" } else { "black" }, robot.painted_count(), robot.render()),
+            Err(error) => println!("Robot failed: {}", error),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::{env, fs};
+use intcode::Automaton;
+
+/// Number of addresses listed in the hot-spot report.
+const TOP : usize = 20;
+
+/// Usage: profile <program> [input]...
+/// Runs the program to halt and prints its output and a hot-spot report.
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let path = &args[1];
+    let inputs = args[2..].iter().map(|arg| arg.parse().expect("Input must be a number"));
+
+    let contents = fs::read_to_string(path).expect("Failed to read contents of file");
+
+    let mut automaton : Automaton = Automaton::new();
+    automaton.enable_profiling().load(contents.as_str()).expect("Failed to parse program").push_inputs(inputs);
+    automaton.run_to_halt().expect("Program failed");
+
+    println!("output: {:?}", automaton.get_output());
+    print!("{}", automaton.get_profile().unwrap().report(&automaton, TOP));
+}
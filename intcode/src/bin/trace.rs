@@ -0,0 +1,24 @@
+use std::{env, fs, io};
+use intcode::{Automaton, JsonTracer, LogTracer};
+
+/// Usage: trace <program> [--log] [input]...
+/// Writes a JSON Lines trace (or a readable log with --log) to stdout.
+fn main() {
+    let args : Vec<String> = env::args().collect();
+    let path = &args[1];
+    let log = args.iter().any(|arg| arg == "--log");
+    let inputs = args[2..].iter()
+        .filter(|arg| *arg != "--log")
+        .map(|arg| arg.parse().expect("Input must be a number"));
+
+    let contents = fs::read_to_string(path).expect("Failed to read contents of file");
+
+    let mut automaton : Automaton = Automaton::new();
+    if log {
+        automaton.set_tracer(Box::new(LogTracer::new(io::stdout())));
+    } else {
+        automaton.set_tracer(Box::new(JsonTracer::new(io::stdout())));
+    }
+    automaton.load(contents.as_str()).expect("Failed to parse program").push_inputs(inputs);
+    automaton.run_to_halt().expect("Program failed");
+}
@@ -0,0 +1,122 @@
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::convert::TryFrom;
+
+/// A single memory cell of the Intcode machine.
+///
+/// Arithmetic is checked: an operation that does not fit in the cell type
+/// returns `None`, which the `Automaton` reports as an overflow error.
+pub trait Cell : Clone + PartialEq + PartialOrd + Debug + Display + FromStr {
+    fn zero() -> Self;
+    fn one() -> Self;
+    /// Converts `value`, or returns `None` when it does not fit in the cell type.
+    fn from_i64(value : i64) -> Option<Self>;
+    fn to_i64(&self) -> Option<i64>;
+    fn checked_add(&self, other : &Self) -> Option<Self>;
+    fn checked_mul(&self, other : &Self) -> Option<Self>;
+
+    fn to_address(&self) -> Option<usize> {
+        self.to_i64().and_then(|value| usize::try_from(value).ok())
+    }
+}
+
+macro_rules! impl_primitive_cell {
+    ($($t:ty),*) => {$(
+        impl Cell for $t {
+            fn zero() -> Self {
+                0
+            }
+
+            fn one() -> Self {
+                1
+            }
+
+            fn from_i64(value : i64) -> Option<Self> {
+                <$t>::try_from(value).ok()
+            }
+
+            fn to_i64(&self) -> Option<i64> {
+                Some(i64::from(*self))
+            }
+
+            fn checked_add(&self, other : &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *other)
+            }
+
+            fn checked_mul(&self, other : &Self) -> Option<Self> {
+                <$t>::checked_mul(*self, *other)
+            }
+        }
+    )*};
+}
+
+impl_primitive_cell!(i32, i64);
+
+#[cfg(feature = "bigint")]
+mod bigint {
+    use num_bigint::BigInt;
+    use num_traits::{One, ToPrimitive, Zero};
+    use crate::Cell;
+
+    impl Cell for BigInt {
+        fn zero() -> Self {
+            Zero::zero()
+        }
+
+        fn one() -> Self {
+            One::one()
+        }
+
+        fn from_i64(value : i64) -> Option<Self> {
+            Some(BigInt::from(value))
+        }
+
+        fn to_i64(&self) -> Option<i64> {
+            ToPrimitive::to_i64(self)
+        }
+
+        fn checked_add(&self, other : &Self) -> Option<Self> {
+            Some(self + other)
+        }
+
+        fn checked_mul(&self, other : &Self) -> Option<Self> {
+            Some(self * other)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Cell;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(Cell::checked_add(&2i64, &3), Some(5));
+        assert_eq!(Cell::checked_mul(&i64::MAX, &2), None);
+        assert_eq!(Cell::checked_mul(&i32::MAX, &2), None);
+    }
+
+    #[test]
+    fn test_from_i64() {
+        assert_eq!(<i32 as Cell>::from_i64(-7), Some(-7));
+        assert_eq!(<i32 as Cell>::from_i64(4294967296), None);
+        assert_eq!(<i64 as Cell>::from_i64(i64::MIN), Some(i64::MIN));
+    }
+
+    #[test]
+    fn test_to_address() {
+        assert_eq!(42i64.to_address(), Some(42));
+        assert_eq!((-1i64).to_address(), None);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_does_not_overflow() {
+        use num_bigint::BigInt;
+
+        let big = BigInt::from(i64::MAX);
+        let product = big.checked_mul(&BigInt::from(4)).unwrap();
+        assert_eq!(product.to_string(), "36893488147419103228");
+        assert_eq!(product.to_address(), None);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fmt::Write;
+use crate::{Automaton, Cell, ErrorKind, Instruction, ParameterMode};
+
+/// Where control can go after a basic block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Successor {
+    /// Execution continues with the instruction after the block.
+    Next(usize),
+    /// A jump to an immediate target.
+    Jump(usize),
+    /// A jump whose target is only known at run time.
+    Unresolved,
+}
+
+/// Why a basic block stops before reaching a branch or a halt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    /// The cell does not decode as an instruction.
+    Decode(ErrorKind),
+    /// The program image ends before the last parameter of the instruction.
+    Truncated,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockError::Decode(kind) => write!(f, "{}", kind),
+            BlockError::Truncated => write!(f, "instruction runs past the end of the image"),
+        }
+    }
+}
+
+/// A run of instructions that is only entered at its first instruction
+/// and only left after its last one.
+#[derive(Debug, Clone)]
+pub struct BasicBlock<T = i64> {
+    pub start : usize,
+    pub instructions : Vec<(usize, Instruction<T>)>,
+    pub successors : Vec<Successor>,
+    /// Set when the block runs into a cell that does not decode, typically
+    /// code the program writes before executing it.
+    pub error : Option<BlockError>,
+}
+
+/// The control-flow graph of a program image, found statically by following
+/// execution from address 0. Jumps with immediate targets are followed;
+/// other jumps end in an unresolved edge. A jump whose condition is an
+/// immediate value is treated as always or never taken.
+pub struct ControlFlowGraph<T = i64> {
+    blocks : BTreeMap<usize, BasicBlock<T>>,
+}
+
+impl<T : Cell> ControlFlowGraph<T> {
+    pub fn new(image : &[T]) -> ControlFlowGraph<T> {
+        let mut automaton = Automaton::new();
+        automaton.load_memory(image.to_vec());
+        let decode = |address : usize| match automaton.decode_at(address) {
+            Ok(instruction) if address + instruction.params.len() < image.len() => Ok(instruction),
+            Ok(_) => Err(BlockError::Truncated),
+            Err(error) => Err(BlockError::Decode(error.kind)),
+        };
+
+        // find the reachable instructions that start a block
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        let mut visited = HashSet::new();
+        let mut pending = vec![0];
+        while let Some(address) = pending.pop() {
+            if !visited.insert(address) {
+                continue;
+            }
+            let instruction = match decode(address) {
+                Ok(instruction) => instruction,
+                Err(_) => continue,
+            };
+            let branch = is_branch(&instruction);
+            for successor in successors(address, &instruction) {
+                match successor {
+                    Successor::Next(next) => {
+                        if branch {
+                            leaders.insert(next);
+                        }
+                        pending.push(next);
+                    },
+                    Successor::Jump(target) => {
+                        leaders.insert(target);
+                        pending.push(target);
+                    },
+                    Successor::Unresolved => (),
+                }
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        for start in leaders.iter().copied() {
+            let mut block = BasicBlock { start, instructions : Vec::new(), successors : Vec::new(), error : None };
+            let mut address = start;
+            loop {
+                let instruction = match decode(address) {
+                    Ok(instruction) => instruction,
+                    Err(error) => {
+                        block.error = Some(error);
+                        break;
+                    },
+                };
+                let next = address + instruction.params.len() + 1;
+                let ends = is_branch(&instruction) || instruction.opcode == 99;
+                let found = successors(address, &instruction);
+                block.instructions.push((address, instruction));
+                if ends {
+                    block.successors = found;
+                    break;
+                }
+                if leaders.contains(&next) {
+                    block.successors = vec![Successor::Next(next)];
+                    break;
+                }
+                address = next;
+            }
+            blocks.insert(start, block);
+        }
+        ControlFlowGraph { blocks }
+    }
+
+    pub fn get_blocks(&self) -> impl Iterator<Item = &BasicBlock<T>> {
+        self.blocks.values()
+    }
+
+    pub fn get_block(&self, start : usize) -> Option<&BasicBlock<T>> {
+        self.blocks.get(&start)
+    }
+
+    /// The graph in Graphviz DOT format. Blocks are labelled with their
+    /// disassembly; unresolved jumps lead to a dashed `?` node and blocks
+    /// that run into undecodable cells are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (address, instruction) in &block.instructions {
+                let _ = write!(label, "{:>5}: {}\\l", address, instruction);
+            }
+            match &block.error {
+                Some(kind) => {
+                    let _ = write!(label, "<{}>\\l", kind);
+                    let _ = writeln!(dot, "    b{} [label=\"{}\", color=red];", block.start, label);
+                },
+                None => {
+                    let _ = writeln!(dot, "    b{} [label=\"{}\"];", block.start, label);
+                },
+            }
+            for successor in &block.successors {
+                let _ = match successor {
+                    Successor::Next(next) => writeln!(dot, "    b{} -> b{};", block.start, next),
+                    Successor::Jump(target) => writeln!(dot, "    b{} -> b{} [label=\"jump\"];", block.start, target),
+                    Successor::Unresolved => writeln!(dot,
+                        "    u{0} [label=\"?\", shape=circle, style=dashed];\n    b{0} -> u{0} [label=\"jump\", style=dashed];",
+                        block.start),
+                };
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn is_branch<T>(instruction : &Instruction<T>) -> bool {
+    instruction.opcode == 5 || instruction.opcode == 6
+}
+
+fn successors<T : Cell>(address : usize, instruction : &Instruction<T>) -> Vec<Successor> {
+    let next = Successor::Next(address + instruction.params.len() + 1);
+    if instruction.opcode == 99 {
+        return Vec::new();
+    }
+    if !is_branch(instruction) {
+        return vec![next];
+    }
+
+    let (condition, target) = (&instruction.params[0], &instruction.params[1]);
+    let jump = match target.mode {
+        ParameterMode::Immediate => target.param.to_address().map_or(Successor::Unresolved, Successor::Jump),
+        _ => Successor::Unresolved,
+    };
+    match condition.mode {
+        ParameterMode::Immediate => {
+            let taken = (condition.param != T::zero()) == (instruction.opcode == 5);
+            vec![if taken { jump } else { next }]
+        },
+        _ => vec![next, jump],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_program, BlockError, ControlFlowGraph, Successor};
+
+    #[test]
+    fn test_blocks() {
+        // skips the first output for a non-zero input
+        let image : Vec<i64> = parse_program("3,12,1005,12,7,104,1,104,2,99,5,6,0").unwrap();
+        let cfg = ControlFlowGraph::new(&image);
+        let starts : Vec<usize> = cfg.get_blocks().map(|block| block.start).collect();
+        assert_eq!(starts, vec![0, 5, 7]);
+        assert_eq!(cfg.get_block(0).unwrap().successors, vec![Successor::Next(5), Successor::Jump(7)]);
+        assert_eq!(cfg.get_block(5).unwrap().successors, vec![Successor::Next(7)]);
+        assert_eq!(cfg.get_block(7).unwrap().instructions.len(), 2);
+        assert!(cfg.get_block(7).unwrap().successors.is_empty());
+    }
+
+    #[test]
+    fn test_constant_conditions_and_errors() {
+        // an unconditional jump over a cell that is not code, into a block
+        // whose last instruction is cut off by the end of the image
+        let image : Vec<i64> = parse_program("1105,1,4,-1,1106,1,0,1").unwrap();
+        let cfg = ControlFlowGraph::new(&image);
+        assert_eq!(cfg.get_block(0).unwrap().successors, vec![Successor::Jump(4)]);
+        assert_eq!(cfg.get_block(4).unwrap().successors, vec![Successor::Next(7)]);
+        assert_eq!(cfg.get_block(7).unwrap().error, Some(BlockError::Truncated));
+        assert!(cfg.get_block(3).is_none());
+    }
+
+    #[test]
+    fn test_dot() {
+        let image : Vec<i64> = parse_program("3,7,6,7,7,99,0,0").unwrap();
+        assert_eq!(ControlFlowGraph::new(&image).to_dot(), "\
+digraph cfg {
+    node [shape=box, fontname=\"monospace\"];
+    b0 [label=\"    0: in [7]\\l    2: jf [7], [7]\\l\"];
+    b0 -> b5;
+    u0 [label=\"?\", shape=circle, style=dashed];
+    b0 -> u0 [label=\"jump\", style=dashed];
+    b5 [label=\"    5: hlt\\l\"];
+}
+");
+    }
+}
@@ -0,0 +1,490 @@
+use std::rc::Rc;
+use crate::{Automaton, Cell, ErrorKind, Instruction, IntcodeError, Parameter, ParameterMode, ParseError, Status};
+
+/// A compiled instruction; returns the address it wrote to, if any.
+type Op<T> = Box<dyn Fn(&mut Automaton<T>) -> Result<Option<usize>, IntcodeError>>;
+/// A compiled jump, output or halt that ends a block and sets the next PC.
+type Exit<T> = Box<dyn Fn(&mut Automaton<T>) -> Result<Status<T>, IntcodeError>>;
+
+/// Where an operand is read from, resolved when the block is compiled.
+enum Source<T> {
+    Immediate(T),
+    Position(usize),
+    Relative(i64),
+}
+
+impl<T : Cell> Source<T> {
+    fn new(param : &Parameter<T>) -> Option<Source<T>> {
+        match param.mode {
+            ParameterMode::Immediate => Some(Source::Immediate(param.param.clone())),
+            ParameterMode::Position => param.param.to_address().map(Source::Position),
+            ParameterMode::Relative => param.param.to_i64().map(Source::Relative),
+        }
+    }
+
+    fn read(&self, automaton : &Automaton<T>) -> Result<T, IntcodeError> {
+        match self {
+            Source::Immediate(value) => Ok(value.clone()),
+            Source::Position(address) => automaton.read_cell(*address),
+            Source::Relative(offset) => automaton.read_cell(automaton.relative_address(*offset)?),
+        }
+    }
+}
+
+/// Where a result is written to, resolved when the block is compiled.
+enum Target {
+    Position(usize),
+    Relative(i64),
+}
+
+impl Target {
+    fn new<T : Cell>(param : &Parameter<T>) -> Option<Target> {
+        match param.mode {
+            ParameterMode::Immediate => None,
+            ParameterMode::Position => param.param.to_address().map(Target::Position),
+            ParameterMode::Relative => param.param.to_i64().map(Target::Relative),
+        }
+    }
+
+    fn resolve<T : Cell>(&self, automaton : &Automaton<T>) -> Result<usize, IntcodeError> {
+        match self {
+            Target::Position(address) => Ok(*address),
+            Target::Relative(offset) => automaton.relative_address(*offset),
+        }
+    }
+}
+
+/// Straight-line instructions from `start` up to `end`, ending in a jump,
+/// output or halt, or falling through to an instruction that is left to
+/// the interpreter.
+struct Block<T> {
+    start : usize,
+    end : usize,
+    ops : Vec<(usize, usize, Op<T>)>,
+    exit : Option<(usize, Exit<T>)>,
+}
+
+enum Entry<T> {
+    Unknown,
+    Compiled(Rc<Block<T>>),
+    Interpreted,
+}
+
+/// Runs an `Automaton` by compiling its program into basic blocks, each a
+/// chain of closures with operands resolved up front, instead of decoding
+/// every instruction as it executes.
+///
+/// Results, errors and instruction counts are the same as for the plain
+/// `Automaton`. Input instructions, instructions whose operands cannot be
+/// resolved ahead of time and blocks the program has written into are run
+/// by the interpreter instead, as is everything while instrumentation such
+/// as tracing or watchpoints is enabled on the automaton.
+pub struct CompiledAutomaton<T = i64> {
+    automaton : Automaton<T>,
+    entries : Vec<Entry<T>>,
+    /// Number of compiled blocks covering each address.
+    code : Vec<u16>,
+}
+
+impl<T : Cell + 'static> Default for CompiledAutomaton<T> {
+    fn default() -> Self {
+        CompiledAutomaton::new()
+    }
+}
+
+impl<T : Cell + 'static> CompiledAutomaton<T> {
+    pub fn new() -> CompiledAutomaton<T> {
+        CompiledAutomaton::from_automaton(Automaton::new())
+    }
+
+    /// Takes over an automaton, e.g. one with a memory limit or input already set.
+    pub fn from_automaton(automaton : Automaton<T>) -> CompiledAutomaton<T> {
+        CompiledAutomaton {
+            automaton,
+            entries : Vec::new(),
+            code : Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, input : &str) -> Result<&mut Self, ParseError> {
+        self.automaton.load(input)?;
+        self.reset();
+
+        Ok(self)
+    }
+
+    pub fn load_memory(&mut self, memory : Vec<T>) -> &mut Self {
+        self.automaton.load_memory(memory);
+        self.reset();
+
+        self
+    }
+
+    pub fn get_automaton(&self) -> &Automaton<T> {
+        &self.automaton
+    }
+
+    pub fn into_automaton(self) -> Automaton<T> {
+        self.automaton
+    }
+
+    pub fn set_input(&mut self, input : T) -> &mut Self {
+        self.automaton.set_input(input);
+
+        self
+    }
+
+    pub fn push_input(&mut self, input : T) -> &mut Self {
+        self.automaton.push_input(input);
+
+        self
+    }
+
+    pub fn push_inputs<I : IntoIterator<Item = T>>(&mut self, inputs : I) -> &mut Self {
+        self.automaton.push_inputs(inputs);
+
+        self
+    }
+
+    pub fn write_memory(&mut self, address : usize, value : T) -> Result<(), IntcodeError> {
+        self.automaton.write_memory(address, value)?;
+        self.invalidate(address);
+        Ok(())
+    }
+
+    pub fn dump_memory(&self) -> &[T] {
+        self.automaton.dump_memory()
+    }
+
+    pub fn get_last_output(&self) -> T {
+        self.automaton.get_last_output()
+    }
+
+    pub fn get_output(&self) -> &[T] {
+        self.automaton.get_output()
+    }
+
+    pub fn take_output(&mut self) -> Vec<T> {
+        self.automaton.take_output()
+    }
+
+    /// Executes the basic block at the PC, or a single instruction where
+    /// the interpreter is used. Returns the same statuses as `Automaton::step`.
+    pub fn step(&mut self) -> Result<Status<T>, IntcodeError> {
+        if self.automaton.is_finished() {
+            return Ok(Status::Halted);
+        }
+        let pc = self.automaton.get_pc();
+        if self.automaton.has_hooks() || pc >= self.automaton.dump_memory().len() {
+            return self.interpret();
+        }
+        if pc >= self.entries.len() {
+            let length = self.automaton.dump_memory().len();
+            self.entries.resize_with(length, || Entry::Unknown);
+            self.code.resize(length, 0);
+        }
+        if let Entry::Unknown = self.entries[pc] {
+            self.entries[pc] = match self.compile(pc) {
+                Some(block) => {
+                    if block.end > self.code.len() {
+                        self.code.resize(block.end, 0);
+                    }
+                    for count in &mut self.code[block.start..block.end] {
+                        *count += 1;
+                    }
+                    Entry::Compiled(Rc::new(block))
+                },
+                None => Entry::Interpreted,
+            };
+        }
+        match &self.entries[pc] {
+            Entry::Compiled(block) => {
+                let block = block.clone();
+                self.run_block(&block)
+            },
+            _ => self.interpret(),
+        }
+    }
+
+    /// Runs until the machine needs input, produces an output or halts.
+    pub fn run(&mut self) -> Result<Status<T>, IntcodeError> {
+        loop {
+            match self.step()? {
+                Status::Running => (),
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Runs until the machine halts, collecting all output. Running out of
+    /// input is an error.
+    pub fn run_to_halt(&mut self) -> Result<&mut Self, IntcodeError> {
+        loop {
+            match self.run()? {
+                Status::Output(_) | Status::Watchpoint(_) | Status::Running => (),
+                Status::NeedsInput => return Err(self.automaton.error(ErrorKind::NoInput)),
+                Status::Halted => return Ok(self),
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.entries.clear();
+        self.code.clear();
+    }
+
+    fn interpret(&mut self) -> Result<Status<T>, IntcodeError> {
+        let target = self.automaton.decode_at(self.automaton.get_pc()).ok()
+            .and_then(|instruction| self.automaton.write_target(&instruction));
+        let status = self.automaton.step()?;
+        if let (Some(address), false) = (target, status == Status::NeedsInput) {
+            self.invalidate(address);
+        }
+        Ok(status)
+    }
+
+    fn run_block(&mut self, block : &Block<T>) -> Result<Status<T>, IntcodeError> {
+        for (pc, next, op) in &block.ops {
+            self.automaton.set_pc(*pc);
+            let written = op(&mut self.automaton)?;
+            self.automaton.count_instruction();
+            if let Some(address) = written {
+                if self.is_code(address) {
+                    // the program rewrote compiled code, possibly this block
+                    self.invalidate(address);
+                    self.automaton.set_pc(*next);
+                    return Ok(Status::Running);
+                }
+            }
+        }
+        match &block.exit {
+            Some((pc, exit)) => {
+                self.automaton.set_pc(*pc);
+                let status = exit(&mut self.automaton)?;
+                self.automaton.count_instruction();
+                Ok(status)
+            },
+            None => {
+                self.automaton.set_pc(block.end);
+                Ok(Status::Running)
+            },
+        }
+    }
+
+    /// Hands every compiled block containing `address` over to the interpreter.
+    fn invalidate(&mut self, address : usize) {
+        if !self.is_code(address) {
+            return;
+        }
+        for entry in self.entries.iter_mut() {
+            let covers = match entry {
+                Entry::Compiled(block) => (block.start..block.end).contains(&address),
+                _ => false,
+            };
+            if covers {
+                if let Entry::Compiled(block) = std::mem::replace(entry, Entry::Interpreted) {
+                    for count in &mut self.code[block.start..block.end] {
+                        *count -= 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_code(&self, address : usize) -> bool {
+        self.code.get(address).is_some_and(|count| *count > 0)
+    }
+
+    fn compile(&self, start : usize) -> Option<Block<T>> {
+        let mut block = Block { start, end : start, ops : Vec::new(), exit : None };
+        let mut pc = start;
+        while let Ok(instruction) = self.automaton.decode_at(pc) {
+            let next = pc + instruction.params.len() + 1;
+            if let Some(exit) = compile_exit(&instruction, next) {
+                block.exit = Some((pc, exit));
+                block.end = next;
+                break;
+            }
+            match compile_op(&instruction) {
+                Some(op) => block.ops.push((pc, next, op)),
+                None => break,
+            }
+            pc = next;
+            block.end = next;
+        }
+        if block.ops.is_empty() && block.exit.is_none() {
+            return None;
+        }
+        Some(block)
+    }
+}
+
+fn compile_op<T : Cell + 'static>(instruction : &Instruction<T>) -> Option<Op<T>> {
+    let params = &instruction.params;
+    let compute : fn(&T, &T) -> Option<T> = match instruction.opcode {
+        1 => |a, b| a.checked_add(b),
+        2 => |a, b| a.checked_mul(b),
+        7 => |a, b| Some(if a < b { T::one() } else { T::zero() }),
+        8 => |a, b| Some(if a == b { T::one() } else { T::zero() }),
+        9 => {
+            let offset = Source::new(&params[0])?;
+            return Some(Box::new(move |automaton| {
+                let offset = offset.read(automaton)?;
+                automaton.adjust_relative_base(&offset)?;
+                Ok(None)
+            }));
+        },
+        _ => return None,
+    };
+    let (a, b, target) = (Source::new(&params[0])?, Source::new(&params[1])?, Target::new(&params[2])?);
+    Some(Box::new(move |automaton| {
+        let a = a.read(automaton)?;
+        let b = b.read(automaton)?;
+        let address = target.resolve(automaton)?;
+        let result = compute(&a, &b).ok_or_else(|| automaton.error(ErrorKind::Overflow))?;
+        automaton.write(address, result)?;
+        Ok(Some(address))
+    }))
+}
+
+fn compile_exit<T : Cell + 'static>(instruction : &Instruction<T>, next : usize) -> Option<Exit<T>> {
+    let params = &instruction.params;
+    match instruction.opcode {
+        4 => {
+            let value = Source::new(&params[0])?;
+            Some(Box::new(move |automaton| {
+                let value = value.read(automaton)?;
+                automaton.emit(value.clone());
+                automaton.set_pc(next);
+                Ok(Status::Output(value))
+            }))
+        },
+        5 | 6 => {
+            let jump_if = instruction.opcode == 5;
+            let (condition, target) = (Source::new(&params[0])?, Source::new(&params[1])?);
+            Some(Box::new(move |automaton| {
+                let condition = condition.read(automaton)?;
+                let target = target.read(automaton)?;
+                let pc = match (condition != T::zero()) == jump_if {
+                    true => automaton.get_jump_target(&target)?,
+                    false => next,
+                };
+                automaton.set_pc(pc);
+                Ok(Status::Running)
+            }))
+        },
+        99 => Some(Box::new(|automaton| {
+            automaton.halt();
+            Ok(Status::Halted)
+        })),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Automaton, CompiledAutomaton, IntcodeError, Status};
+
+    const DIAGNOSTIC : &str = include_str!("../../resources/5_TEST_diagnostic.txt");
+
+    /// Programs and inputs from the interpreter's test suite.
+    const FIXTURES : &[(&str, &[i64])] = &[
+        ("1,0,0,0,99", &[]),
+        ("2,3,0,3,99", &[]),
+        ("2,4,4,5,99,0", &[]),
+        ("1,1,1,4,99,5,6,0,99", &[]),
+        ("1002,4,3,4,33", &[]),
+        ("1101,100,-1,4,0", &[]),
+        ("3,0,4,0,99", &[42]),
+        ("3,9,8,9,10,9,4,9,99,-1,8", &[8]),
+        ("3,9,8,9,10,9,4,9,99,-1,8", &[1]),
+        ("3,9,7,9,10,9,4,9,99,-1,8", &[7]),
+        ("3,9,7,9,10,9,4,9,99,-1,8", &[9]),
+        ("3,3,1108,-1,8,3,4,3,99", &[8]),
+        ("3,3,1108,-1,8,3,4,3,99", &[1]),
+        ("3,3,1107,-1,8,3,4,3,99", &[7]),
+        ("3,3,1107,-1,8,3,4,3,99", &[9]),
+        ("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[0]),
+        ("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[1]),
+        ("3,3,1105,-1,9,1101,0,0,12,4,12,99,1", &[0]),
+        ("3,3,1105,-1,9,1101,0,0,12,4,12,99,1", &[1]),
+        ("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99", &[7]),
+        ("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99", &[8]),
+        ("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99", &[9]),
+        ("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99", &[]),
+        ("1102,34915192,34915192,7,4,7,99,0", &[]),
+        ("104,1125899906842624,99", &[]),
+        ("109,11,22201,0,1,2,209,2,204,-5,99,3,4,0", &[]),
+        ("109,7,203,1,204,1,99,0,0", &[42]),
+        ("1102,9223372036854775807,2,7,4,7,99,0", &[]),
+        ("1101,2,3,100000000,4,100000000,99", &[]),
+        ("1101,2,3,10,4,10,4,2000,99", &[]),
+        ("4,20,1001,1,1,1,1001,22,-1,22,1005,22,0,99,0,0,0,0,0,0,7,8,2", &[]),
+        ("3,0,3,1,99", &[1]),
+        ("1101,1,1,5,98,0", &[]),
+        ("1101,1,1,5,1", &[]),
+        ("11101,1,1,0,99", &[]),
+        ("301,0,0,0,99", &[]),
+        ("1,-1,0,0,99", &[]),
+        ("109,-5,203,2,99", &[1]),
+        ("1105,1,-5", &[]),
+        ("1105,1,20", &[]),
+        ("-1", &[]),
+    ];
+
+    type Outcome = Result<(Vec<i64>, Vec<i64>, u64), IntcodeError>;
+
+    fn interpreted(program : &str, inputs : &[i64]) -> Outcome {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load(program).unwrap().push_inputs(inputs.to_vec());
+        automaton.run_to_halt()?;
+        Ok((automaton.get_output().to_vec(), automaton.dump_memory().to_vec(), automaton.get_instruction_count()))
+    }
+
+    fn compiled(program : &str, inputs : &[i64]) -> Outcome {
+        let mut compiled : CompiledAutomaton = CompiledAutomaton::new();
+        compiled.load(program).unwrap().push_inputs(inputs.to_vec());
+        compiled.run_to_halt()?;
+        let automaton = compiled.get_automaton();
+        Ok((automaton.get_output().to_vec(), automaton.dump_memory().to_vec(), automaton.get_instruction_count()))
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        for (program, inputs) in FIXTURES.iter() {
+            assert_eq!(compiled(program, inputs), interpreted(program, inputs), "program {}", program);
+        }
+    }
+
+    #[test]
+    fn test_diagnostic() {
+        for input in &[1, 5] {
+            assert_eq!(compiled(DIAGNOSTIC, &[*input]), interpreted(DIAGNOSTIC, &[*input]));
+        }
+        assert_eq!(compiled(DIAGNOSTIC, &[5]).unwrap().0, vec![12410607]);
+    }
+
+    #[test]
+    fn test_pauses_like_interpreter() {
+        let mut compiled : CompiledAutomaton = CompiledAutomaton::new();
+        compiled.load("3,7,4,7,1105,1,0,0").unwrap();
+        assert_eq!(compiled.run().unwrap(), Status::NeedsInput);
+        compiled.push_input(5);
+        assert_eq!(compiled.run().unwrap(), Status::Output(5));
+        assert_eq!(compiled.run().unwrap(), Status::NeedsInput);
+    }
+
+    #[test]
+    fn test_self_modified_block_is_interpreted() {
+        // the add at 4 rewrites the output at 8 within the same block
+        let mut compiled : CompiledAutomaton = CompiledAutomaton::new();
+        compiled.load("1101,0,0,12,1101,0,7,9,104,0,99,0,0").unwrap().run_to_halt().unwrap();
+        assert_eq!(compiled.get_output(), &[7]);
+
+        compiled.load("104,1,1105,1,0").unwrap();
+        assert_eq!(compiled.step().unwrap(), Status::Output(1));
+        compiled.step().unwrap();
+        compiled.write_memory(1, 5).unwrap();
+        assert_eq!(compiled.step().unwrap(), Status::Output(5));
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeSet;
+use crate::{disassemble_with, Cell, Instruction};
+
+/// Which addresses a program executed and which it used as data, gathered
+/// while coverage is enabled on an `Automaton`.
+///
+/// An instruction's opcode and parameter cells count as code; cells that
+/// operands read from or that instructions write to count as data.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    executed : BTreeSet<usize>,
+    code : BTreeSet<usize>,
+    data : BTreeSet<usize>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub(crate) fn record_instruction(&mut self, pc : usize, length : usize) {
+        self.executed.insert(pc);
+        self.code.extend(pc..pc + length);
+    }
+
+    pub(crate) fn record_data(&mut self, address : usize) {
+        self.data.insert(address);
+    }
+
+    /// Adds the coverage of another run, e.g. of the same program with different input.
+    pub fn merge(&mut self, other : &Coverage) {
+        self.executed.extend(other.executed.iter());
+        self.code.extend(other.code.iter());
+        self.data.extend(other.data.iter());
+    }
+
+    /// Whether an instruction starting at `address` was executed.
+    pub fn is_executed(&self, address : usize) -> bool {
+        self.executed.contains(&address)
+    }
+
+    /// Whether `address` was read or written as an operand.
+    pub fn is_data(&self, address : usize) -> bool {
+        self.data.contains(&address)
+    }
+
+    pub fn get_executed(&self) -> &BTreeSet<usize> {
+        &self.executed
+    }
+
+    /// Disassembles `image` guided by the coverage, marking every line:
+    ///
+    /// * `X` an executed instruction, or cells of one that the program
+    ///   modified before running it and so do not decode from `image`
+    /// * `-` an instruction that was never executed
+    /// * `D` data the program read or wrote but never executed
+    /// * ` ` cells that were never touched and do not decode
+    pub fn listing<T : Cell>(&self, image : &[T]) -> String {
+        let code = |address : usize, instruction : &Instruction<T>| {
+            let executed = self.is_executed(address);
+            let untouched = (address..=address + instruction.params.len())
+                .all(|cell| !self.code.contains(&cell) && !self.is_data(cell));
+            match (executed, untouched) {
+                (true, _) => Some('X'),
+                (false, true) => Some('-'),
+                (false, false) => None,
+            }
+        };
+        let data = |address : usize| match address {
+            _ if self.is_executed(address) || self.code.contains(&address) => 'X',
+            _ if self.is_data(address) => 'D',
+            _ => ' ',
+        };
+        disassemble_with(image, code, data).iter()
+            .map(|(marker, line)| format!("{} {}\n", marker, line))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_program, Automaton, Coverage};
+
+    const IS_EIGHT : &str = "3,9,8,9,10,9,4,9,99,-1,8";
+
+    fn coverage(program : &str, input : i64) -> Coverage {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_coverage().load(program).unwrap().set_input(input);
+        automaton.run_to_halt().unwrap();
+        automaton.take_coverage().unwrap()
+    }
+
+    #[test]
+    fn test_executed_and_data() {
+        let coverage = coverage(IS_EIGHT, 8);
+        assert_eq!(coverage.get_executed().iter().copied().collect::<Vec<usize>>(), vec![0, 2, 6, 8]);
+        assert!(coverage.is_data(9));
+        assert!(coverage.is_data(10));
+        assert!(!coverage.is_data(0));
+    }
+
+    #[test]
+    fn test_listing() {
+        // jump over an instruction that only runs for a non-zero input
+        let program = "3,12,1005,12,7,104,1,104,2,99,5,6,0";
+        let image : Vec<i64> = parse_program(program).unwrap();
+        assert_eq!(coverage(program, 1).listing(&image), "\
+X     0: 3,12                    in [12]
+X     2: 1005,12,7               jt [12], #7
+-     5: 104,1                   out #1
+X     7: 104,2                   out #2
+X     9: 99                      hlt
+     10: 5,6                     db 5, 6
+D    12: 0                       db 0
+");
+
+        let mut merged = coverage(program, 1);
+        merged.merge(&coverage(program, 0));
+        assert!(merged.listing(&image).contains("X     5: 104,1"));
+
+        // the instruction at 4 is written before it runs
+        let program = "1002,4,3,4,33";
+        let image : Vec<i64> = parse_program(program).unwrap();
+        assert_eq!(coverage(program, 0).listing(&image), "\
+X     0: 1002,4,3,4              mul [4], #3, [4]
+X     4: 33                      db 33
+");
+    }
+}
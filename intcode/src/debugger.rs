@@ -0,0 +1,463 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use crate::{Automaton, Cell, Status, WatchKind, Watchpoint};
+
+/// Number of cells printed per row by the `mem` command.
+const MEMORY_ROW : usize = 8;
+/// Number of instructions the debugger can step back over.
+const HISTORY_LIMIT : usize = 1 << 20;
+
+const HELP : &str = "\
+step [n]            execute n instructions (default 1)      alias: s
+continue            run until a breakpoint, input or halt    alias: c
+back [n]            undo n instructions (default 1)          alias: sb
+lastwrite <addr>    run back to the last write of addr       alias: lw
+rewind <count>      run back until count instructions ran
+break <addr>        set a breakpoint                         alias: b
+delete <addr>       remove a breakpoint                      alias: d
+breakpoints         list breakpoints
+watch <a> [b] [k]   pause on r, w or rw (k) access to a..=b  default: w
+unwatch <addr>      remove the watchpoints covering addr
+watchpoints         list watchpoints
+regs                show pc, relative base and machine state alias: r
+insn [addr]         show the decoded instruction at pc/addr  alias: i
+mem <addr> [len]    print memory cells                       alias: x
+set <addr> <v>...   write values starting at addr
+input <v>...        queue input values
+quit                leave the debugger                       alias: q";
+
+/// A command-driven debugger around an `Automaton`.
+///
+/// Each command given to `execute` returns the text to show the user, so
+/// the debugger can be driven from a terminal with `repl` or from tests.
+/// The debugger records history so execution can be stepped backwards.
+pub struct Debugger<T = i64> {
+    automaton : Automaton<T>,
+    breakpoints : BTreeSet<usize>,
+}
+
+impl<T : Cell> Debugger<T> {
+    pub fn new(mut automaton : Automaton<T>) -> Debugger<T> {
+        automaton.enable_history(Some(HISTORY_LIMIT));
+        Debugger {
+            automaton,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn get_automaton(&self) -> &Automaton<T> {
+        &self.automaton
+    }
+
+    pub fn get_automaton_mut(&mut self) -> &mut Automaton<T> {
+        &mut self.automaton
+    }
+
+    /// Reads commands from `input` until `quit` or end of input, writing
+    /// a prompt and each command's result to `output`.
+    pub fn repl<R : BufRead, W : Write>(&mut self, input : R, mut output : W) -> io::Result<()> {
+        write!(output, "(idb) ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            if matches!(line.trim(), "q" | "quit") {
+                break;
+            }
+            let response = self.execute(&line);
+            if !response.is_empty() {
+                writeln!(output, "{}", response)?;
+            }
+            write!(output, "(idb) ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Executes one debugger command and returns its result as text.
+    pub fn execute(&mut self, line : &str) -> String {
+        let words : Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return String::new(),
+        };
+
+        let result = match command {
+            "s" | "step" => self.step(args),
+            "c" | "continue" => Ok(self.cont()),
+            "sb" | "back" => self.back(args),
+            "lw" | "lastwrite" => self.last_write(args),
+            "rewind" => self.rewind(args),
+            "b" | "break" => self.set_breakpoint(args),
+            "d" | "delete" => self.delete_breakpoint(args),
+            "breakpoints" => Ok(self.list_breakpoints()),
+            "watch" => self.set_watchpoint(args),
+            "unwatch" => self.delete_watchpoint(args),
+            "watchpoints" => Ok(self.list_watchpoints()),
+            "r" | "regs" => Ok(self.registers()),
+            "i" | "insn" => self.instruction(args),
+            "x" | "mem" => self.memory(args),
+            "set" => self.set_memory(args),
+            "input" => self.input(args),
+            "h" | "help" => Ok(String::from(HELP)),
+            _ => Err(format!("unknown command '{}', try 'help'", command)),
+        };
+        result.unwrap_or_else(|message| format!("error: {}", message))
+    }
+
+    fn step(&mut self, args : &[&str]) -> Result<String, String> {
+        let count = match args.first() {
+            Some(count) => parse_number(count)?,
+            None => 1,
+        };
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            let stop = self.step_once(&mut lines);
+            if stop {
+                break;
+            }
+        }
+        lines.push(self.current_instruction());
+        Ok(lines.join("\n"))
+    }
+
+    fn cont(&mut self) -> String {
+        let mut lines = Vec::new();
+        loop {
+            if self.step_once(&mut lines) {
+                break;
+            }
+            if self.breakpoints.contains(&self.automaton.get_pc()) {
+                lines.push(format!("breakpoint at {}", self.automaton.get_pc()));
+                break;
+            }
+        }
+        lines.push(self.current_instruction());
+        lines.join("\n")
+    }
+
+    fn back(&mut self, args : &[&str]) -> Result<String, String> {
+        let count = match args.first() {
+            Some(count) => parse_number(count)?,
+            None => 1,
+        };
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            if !self.automaton.step_back() {
+                lines.push(String::from("at the start of the recorded history"));
+                break;
+            }
+        }
+        lines.push(self.current_instruction());
+        Ok(lines.join("\n"))
+    }
+
+    fn last_write(&mut self, args : &[&str]) -> Result<String, String> {
+        let address = parse_address(args)?;
+        if !self.automaton.run_back_to_write(address) {
+            return Err(format!("no recorded write to {}", address));
+        }
+        Ok(format!("last write to {} at instruction {}\n{}",
+                   address, self.automaton.get_instruction_count(), self.current_instruction()))
+    }
+
+    fn rewind(&mut self, args : &[&str]) -> Result<String, String> {
+        let count = match args.first() {
+            Some(count) => parse_number(count)?,
+            None => return Err(String::from("missing instruction count")),
+        };
+        if !self.automaton.rewind_to(count as u64) {
+            return Err(format!("cannot rewind to instruction {}", count));
+        }
+        Ok(self.current_instruction())
+    }
+
+    /// Executes one instruction, describing anything noteworthy in `lines`.
+    /// Returns whether execution cannot simply continue.
+    fn step_once(&mut self, lines : &mut Vec<String>) -> bool {
+        let output_len = self.automaton.get_output().len();
+        match self.automaton.step() {
+            Ok(Status::Running) => false,
+            Ok(Status::Output(value)) => {
+                lines.push(format!("output: {}", value));
+                false
+            },
+            Ok(Status::Watchpoint(event)) => {
+                lines.push(format!("watch: {}", event));
+                // an output instruction that hits a watchpoint still outputs
+                for value in self.automaton.get_output().iter().skip(output_len) {
+                    lines.push(format!("output: {}", value));
+                }
+                true
+            },
+            Ok(Status::NeedsInput) => {
+                lines.push(String::from("waiting for input"));
+                true
+            },
+            Ok(Status::Halted) => {
+                lines.push(String::from("halted"));
+                true
+            },
+            Err(error) => {
+                lines.push(format!("error: {}", error));
+                true
+            },
+        }
+    }
+
+    fn set_breakpoint(&mut self, args : &[&str]) -> Result<String, String> {
+        let address = parse_address(args)?;
+        self.breakpoints.insert(address);
+        Ok(format!("breakpoint set at {}", address))
+    }
+
+    fn delete_breakpoint(&mut self, args : &[&str]) -> Result<String, String> {
+        let address = parse_address(args)?;
+        if self.breakpoints.remove(&address) {
+            Ok(format!("breakpoint at {} deleted", address))
+        } else {
+            Err(format!("no breakpoint at {}", address))
+        }
+    }
+
+    fn list_breakpoints(&self) -> String {
+        if self.breakpoints.is_empty() {
+            return String::from("no breakpoints");
+        }
+        self.breakpoints.iter().map(|address| address.to_string()).collect::<Vec<String>>().join(", ")
+    }
+
+    fn set_watchpoint(&mut self, args : &[&str]) -> Result<String, String> {
+        let start = parse_address(args)?;
+        let mut end = start;
+        let mut kind = WatchKind::Write;
+        for arg in &args[1..] {
+            match *arg {
+                "r" => kind = WatchKind::Read,
+                "w" => kind = WatchKind::Write,
+                "rw" => kind = WatchKind::Access,
+                _ => end = parse_number(arg)?,
+            }
+        }
+        if end < start {
+            return Err(format!("watch range {}..={} is empty", start, end));
+        }
+        let after = end.checked_add(1).ok_or_else(|| format!("watch range {}..={} overflows", start, end))?;
+        self.automaton.add_watchpoint(Watchpoint::new(start..after, kind));
+        Ok(format!("watchpoint set on {}", describe_watchpoint(start, end, kind)))
+    }
+
+    fn delete_watchpoint(&mut self, args : &[&str]) -> Result<String, String> {
+        let address = parse_address(args)?;
+        match self.automaton.remove_watchpoints(address) {
+            0 => Err(format!("no watchpoint on {}", address)),
+            removed => Ok(format!("{} watchpoint(s) on {} deleted", removed, address)),
+        }
+    }
+
+    fn list_watchpoints(&self) -> String {
+        let watchpoints = self.automaton.get_watchpoints();
+        if watchpoints.is_empty() {
+            return String::from("no watchpoints");
+        }
+        watchpoints.iter()
+            .map(|watchpoint| describe_watchpoint(watchpoint.range.start, watchpoint.range.end - 1, watchpoint.kind))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn registers(&self) -> String {
+        let state = if self.automaton.is_finished() { "halted" } else { "running" };
+        format!("pc {}  rb {}  {}  pending input {}",
+                self.automaton.get_pc(),
+                self.automaton.get_relative_base(),
+                state,
+                self.automaton.get_input().len())
+    }
+
+    fn instruction(&self, args : &[&str]) -> Result<String, String> {
+        match args.first() {
+            Some(address) => Ok(self.describe(parse_number(address)?)),
+            None => Ok(self.current_instruction()),
+        }
+    }
+
+    fn current_instruction(&self) -> String {
+        self.describe(self.automaton.get_pc())
+    }
+
+    fn describe(&self, address : usize) -> String {
+        match self.automaton.decode_at(address) {
+            Ok(instruction) => format!("{:>5}: {}", address, instruction),
+            Err(error) => format!("{:>5}: <{}>", address, error.kind),
+        }
+    }
+
+    fn memory(&self, args : &[&str]) -> Result<String, String> {
+        let start = parse_address(args)?;
+        let length = match args.get(1) {
+            Some(length) => parse_number(length)?,
+            None => MEMORY_ROW,
+        };
+        let end = start.checked_add(length).ok_or("address range overflows")?;
+        let memory = self.automaton.get_memory();
+        let mut rows = Vec::new();
+        for row_start in (start..end).step_by(MEMORY_ROW) {
+            let row_end = row_start.saturating_add(MEMORY_ROW).min(end);
+            let values = (row_start..row_end)
+                .map(|address| memory.read(address).map(|value| value.to_string()).map_err(|kind| kind.to_string()))
+                .collect::<Result<Vec<String>, String>>()?;
+            rows.push(format!("{:>5}: {}", row_start, values.join(" ")));
+        }
+        Ok(rows.join("\n"))
+    }
+
+    fn set_memory(&mut self, args : &[&str]) -> Result<String, String> {
+        let start = parse_address(args)?;
+        if args.len() < 2 {
+            return Err(String::from("usage: set <addr> <value>..."));
+        }
+        start.checked_add(args.len() - 1).ok_or("address range overflows")?;
+        for (offset, value) in args[1..].iter().enumerate() {
+            let value = parse_cell(value)?;
+            self.automaton.write_memory(start + offset, value).map_err(|error| error.kind.to_string())?;
+        }
+        self.memory(&[args[0], &(args.len() - 1).to_string()])
+    }
+
+    fn input(&mut self, args : &[&str]) -> Result<String, String> {
+        if args.is_empty() {
+            return Err(String::from("usage: input <value>..."));
+        }
+        let values = args.iter().map(|value| parse_cell(value)).collect::<Result<Vec<T>, String>>()?;
+        self.automaton.push_inputs(values);
+        Ok(format!("pending input {}", self.automaton.get_input().len()))
+    }
+}
+
+fn describe_watchpoint(start : usize, end : usize, kind : WatchKind) -> String {
+    let kind = match kind {
+        WatchKind::Read => "r",
+        WatchKind::Write => "w",
+        WatchKind::Access => "rw",
+    };
+    if start == end {
+        format!("{} ({})", start, kind)
+    } else {
+        format!("{}..={} ({})", start, end, kind)
+    }
+}
+
+fn parse_number(text : &str) -> Result<usize, String> {
+    text.parse().map_err(|_| format!("'{}' is not a number", text))
+}
+
+fn parse_address(args : &[&str]) -> Result<usize, String> {
+    match args.first() {
+        Some(address) => parse_number(address),
+        None => Err(String::from("missing address")),
+    }
+}
+
+fn parse_cell<T : Cell>(text : &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("'{}' is not a value", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Automaton, Debugger};
+
+    fn debugger(program : &str) -> Debugger {
+        let mut automaton = Automaton::new();
+        automaton.load(program).unwrap();
+        Debugger::new(automaton)
+    }
+
+    #[test]
+    fn test_step_and_registers() {
+        let mut debugger = debugger("1002,4,3,4,33");
+        assert_eq!(debugger.execute("insn"), "    0: mul [4], #3, [4]");
+        assert_eq!(debugger.execute("step"), "    4: hlt");
+        assert_eq!(debugger.execute("regs"), "pc 4  rb 0  running  pending input 0");
+        assert_eq!(debugger.execute("s 5"), "halted\n    4: hlt");
+        assert_eq!(debugger.execute("r"), "pc 4  rb 0  halted  pending input 0");
+    }
+
+    #[test]
+    fn test_breakpoints_and_input() {
+        let mut debugger = debugger("3,11,1001,11,1,11,4,11,1105,1,0,0");
+        assert_eq!(debugger.execute("b 6"), "breakpoint set at 6");
+        assert_eq!(debugger.execute("c"), "waiting for input\n    0: in [11]");
+        assert_eq!(debugger.execute("input 41 9"), "pending input 2");
+        assert_eq!(debugger.execute("c"), "breakpoint at 6\n    6: out [11]");
+        assert_eq!(debugger.execute("c"), "output: 42\nbreakpoint at 6\n    6: out [11]");
+        assert_eq!(debugger.execute("breakpoints"), "6");
+        assert_eq!(debugger.execute("delete 6"), "breakpoint at 6 deleted");
+        assert_eq!(debugger.execute("c"), "output: 10\nwaiting for input\n    0: in [11]");
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut debugger = debugger("1,0,0,0,99");
+        assert_eq!(debugger.execute("mem 0 5"), "    0: 1 0 0 0 99");
+        assert_eq!(debugger.execute("x 2 10"), "    2: 0 0 99 0 0 0 0 0\n   10: 0 0");
+        assert_eq!(debugger.execute("set 1 4 4"), "    1: 4 4");
+        debugger.execute("c");
+        assert_eq!(debugger.execute("x 0 5"), "    0: 198 4 4 0 99");
+        assert_eq!(debugger.execute("i 4"), "    4: hlt");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut debugger = debugger("98");
+        assert_eq!(debugger.execute("frobnicate"), "error: unknown command 'frobnicate', try 'help'");
+        assert_eq!(debugger.execute("b"), "error: missing address");
+        assert_eq!(debugger.execute("x foo"), "error: 'foo' is not a number");
+        assert_eq!(debugger.execute("x 18446744073709551615 2"), "error: address range overflows");
+        assert_eq!(debugger.execute("set 18446744073709551615 1 2"), "error: address range overflows");
+        assert_eq!(debugger.execute("insn"), "    0: <unknown opcode>");
+        assert_eq!(debugger.execute("s"), "error: unknown opcode at pc 0 (instruction 98)\n    0: <unknown opcode>");
+        assert_eq!(debugger.execute(""), "");
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = debugger("1002,4,3,4,33");
+        assert_eq!(debugger.execute("watch 4"), "watchpoint set on 4 (w)");
+        assert_eq!(debugger.execute("watch 0 3 r"), "watchpoint set on 0..=3 (r)");
+        assert_eq!(debugger.execute("watchpoints"), "4 (w)\n0..=3 (r)");
+        assert_eq!(debugger.execute("c"), "watch: write [4] 33 -> 99 at pc 0\n    4: hlt");
+        assert_eq!(debugger.execute("unwatch 4"), "1 watchpoint(s) on 4 deleted");
+        assert_eq!(debugger.execute("unwatch 4"), "error: no watchpoint on 4");
+        assert_eq!(debugger.execute("watch 5 2"), "error: watch range 5..=2 is empty");
+        assert_eq!(debugger.execute("watch 0 18446744073709551615"), "error: watch range 0..=18446744073709551615 overflows");
+    }
+
+    #[test]
+    fn test_output_at_watchpoint() {
+        let mut debugger = debugger("4,3,99,42");
+        debugger.execute("watch 3 3 r");
+        assert_eq!(debugger.execute("c"), "watch: read [3] = 42 at pc 0\noutput: 42\n    2: hlt");
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut debugger = debugger("1,0,0,9,1002,9,3,9,99,0");
+        assert_eq!(debugger.execute("c"), "halted\n    8: hlt");
+        assert_eq!(debugger.execute("lw 9"), "last write to 9 at instruction 1\n    4: mul [9], #3, [9]");
+        assert_eq!(debugger.execute("x 9 1"), "    9: 2");
+        assert_eq!(debugger.execute("back"), "    0: add [0], [0], [9]");
+        assert_eq!(debugger.execute("sb"), "at the start of the recorded history\n    0: add [0], [0], [9]");
+        assert_eq!(debugger.execute("s 2"), "    8: hlt");
+        assert_eq!(debugger.execute("rewind 1"), "    4: mul [9], #3, [9]");
+        assert_eq!(debugger.execute("rewind 5"), "error: cannot rewind to instruction 5");
+        assert_eq!(debugger.execute("lw 3"), "error: no recorded write to 3");
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut debugger = debugger("104,7,99");
+        let mut output = Vec::new();
+        debugger.repl("s\nquit\ns\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "(idb) output: 7\n    2: hlt\n(idb) ");
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use crate::{Automaton, Cell, Instruction};
+
+/// Consecutive undecodable cells are grouped into `db` lines of at most this many values.
+const DATA_PER_LINE : usize = 8;
+
+/// One line of a disassembly listing.
+pub enum Line<T = i64> {
+    Code { address : usize, raw : Vec<T>, instruction : Instruction<T> },
+    Data { address : usize, values : Vec<T> },
+}
+
+impl<T> Line<T> {
+    pub fn get_address(&self) -> usize {
+        match self {
+            Line::Code { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+}
+
+impl<T : fmt::Display> fmt::Display for Line<T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Code { address, raw, instruction } =>
+                write!(f, "{:>5}: {:<24}{}", address, join(raw), instruction),
+            Line::Data { address, values } =>
+                write!(f, "{:>5}: {:<24}db {}", address, join(values), join(values).replace(',', ", ")),
+        }
+    }
+}
+
+fn join<T : fmt::Display>(values : &[T]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+/// Decodes a program image from address 0 onwards. Cells that do not decode
+/// to a valid instruction, or whose parameters run past the end of the
+/// image, are emitted as data.
+pub fn disassemble<T : Cell>(image : &[T]) -> Vec<Line<T>> {
+    disassemble_with(image, |_, _| Some(()), |_| ())
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect()
+}
+
+/// Disassembles like `disassemble`, annotating every line. `code` is asked
+/// about each instruction that decodes and may return `None` to have its
+/// cells treated as data instead; `data` annotates a single data cell.
+/// A data run is split where the annotation of its cells changes.
+pub fn disassemble_with<T, A, C, D>(image : &[T], mut code : C, mut data : D) -> Vec<(A, Line<T>)>
+        where T : Cell, A : PartialEq, C : FnMut(usize, &Instruction<T>) -> Option<A>, D : FnMut(usize) -> A {
+    let mut automaton = Automaton::new();
+    automaton.load_memory(image.to_vec());
+
+    let mut lines = Vec::new();
+    let mut run : Option<(A, usize, Vec<T>)> = None;
+    let mut address = 0;
+    while address < image.len() {
+        let decoded = automaton.decode_at(address).ok()
+            .filter(|instruction| address + instruction.params.len() < image.len())
+            .and_then(|instruction| code(address, &instruction).map(|annotation| (annotation, instruction)));
+        if let Some((annotation, instruction)) = decoded {
+            if let Some((annotation, address, values)) = run.take() {
+                lines.push((annotation, Line::Data { address, values }));
+            }
+            let length = instruction.params.len() + 1;
+            lines.push((annotation, Line::Code { address, raw : image[address..address + length].to_vec(), instruction }));
+            address += length;
+            continue;
+        }
+
+        let annotation = data(address);
+        let split = run.as_ref().is_some_and(|(current, _, values)| *current != annotation || values.len() == DATA_PER_LINE);
+        if split {
+            let (annotation, address, values) = run.take().unwrap();
+            lines.push((annotation, Line::Data { address, values }));
+        }
+        run.get_or_insert_with(|| (annotation, address, Vec::new())).2.push(image[address].clone());
+        address += 1;
+    }
+    if let Some((annotation, address, values)) = run {
+        lines.push((annotation, Line::Data { address, values }));
+    }
+    lines
+}
+
+/// The disassembly of `image` as text, one line per instruction or data run.
+pub fn listing<T : Cell>(image : &[T]) -> String {
+    disassemble(image).iter().map(|line| format!("{}\n", line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{disassemble, listing, parse_program};
+
+    #[test]
+    fn test_listing() {
+        let image : Vec<i64> = parse_program("1002,4,3,4,33").unwrap();
+        assert_eq!(listing(&image),
+                   "    0: 1002,4,3,4              mul [4], #3, [4]\n    \
+                        4: 33                      db 33\n");
+    }
+
+    #[test]
+    fn test_all_mnemonics() {
+        let image : Vec<i64> = parse_program("1,0,0,0,2,0,0,0,3,0,4,0,5,0,0,6,0,0,7,0,0,0,8,0,0,0,209,-3,99").unwrap();
+        let mnemonics : Vec<String> = disassemble(&image).iter()
+            .map(|line| line.to_string()[31..].split(' ').next().unwrap().to_string())
+            .collect();
+        assert_eq!(mnemonics, vec!["add", "mul", "in", "out", "jt", "jf", "lt", "eq", "arb", "hlt"]);
+        assert!(listing(&image).contains("   26: 209,-3                  arb rb-3\n"));
+    }
+
+    #[test]
+    fn test_data_sections() {
+        let image : Vec<i64> = parse_program("1105,1,11,-1,0,0,0,0,0,0,-7,204,-1,99,1,2").unwrap();
+        let lines = disassemble(&image);
+        let addresses : Vec<usize> = lines.iter().map(|line| line.get_address()).collect();
+        assert_eq!(addresses, vec![0, 3, 11, 13, 14]);
+        assert_eq!(lines[1].to_string(), "    3: -1,0,0,0,0,0,0,-7       db -1, 0, 0, 0, 0, 0, 0, -7");
+        assert_eq!(lines[2].to_string(), "   11: 204,-1                  out rb-1");
+        // a trailing instruction whose parameters are missing is data
+        assert_eq!(lines[4].to_string(), "   14: 1,2                     db 1, 2");
+    }
+}
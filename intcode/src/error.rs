@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The instruction's opcode is not part of the instruction set.
+    UnknownOpcode,
+    /// A parameter mode digit other than 0, 1 or 2, or a mode given for a
+    /// parameter the instruction does not have.
+    InvalidParameterMode(i64),
+    /// A parameter that is written to is in immediate mode.
+    WriteInImmediateMode,
+    /// A parameter resolved to an address below zero.
+    NegativeAddress(i64),
+    /// The PC, or the target of a jump, is not an address that can be executed.
+    PcOutOfRange(i64),
+    /// An input instruction ran while the input queue was empty.
+    NoInput,
+    /// The machine halted where an output was expected.
+    NoOutput,
+    /// A result does not fit in the cell type.
+    Overflow,
+    /// An address at or above the configured memory limit was accessed.
+    MemoryLimitExceeded(usize),
+    /// The machine waits for input that can never arrive, because every
+    /// machine it is connected to is waiting or has stopped.
+    Deadlock,
+    /// The output the machine writes to no longer accepts values.
+    OutputClosed,
+}
+
+/// A failure while executing an Intcode program, with the PC and raw value
+/// of the instruction that caused it. `instruction` is `None` when the PC
+/// itself could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntcodeError {
+    pub pc : usize,
+    pub instruction : Option<i64>,
+    pub kind : ErrorKind,
+}
+
+impl IntcodeError {
+    pub fn new(pc : usize, instruction : Option<i64>, kind : ErrorKind) -> IntcodeError {
+        IntcodeError { pc, instruction, kind }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownOpcode => write!(f, "unknown opcode"),
+            ErrorKind::InvalidParameterMode(mode) => write!(f, "invalid parameter mode {}", mode),
+            ErrorKind::WriteInImmediateMode => write!(f, "write parameter in immediate mode"),
+            ErrorKind::NegativeAddress(address) => write!(f, "negative address {}", address),
+            ErrorKind::PcOutOfRange(pc) => write!(f, "pc {} is out of range", pc),
+            ErrorKind::NoInput => write!(f, "input queue is empty"),
+            ErrorKind::NoOutput => write!(f, "halted without output"),
+            ErrorKind::Overflow => write!(f, "arithmetic overflow"),
+            ErrorKind::MemoryLimitExceeded(address) => write!(f, "address {} exceeds the memory limit", address),
+            ErrorKind::Deadlock => write!(f, "deadlock, every machine is waiting for input"),
+            ErrorKind::OutputClosed => write!(f, "output is closed"),
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self.instruction {
+            Some(instruction) => write!(f, "{} at pc {} (instruction {})", self.kind, self.pc, instruction),
+            None => write!(f, "{} at pc {}", self.kind, self.pc),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
+
+/// A cell of a program image that is not a number, or does not fit in the
+/// cell type, with its index in the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub index : usize,
+    pub token : String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cell '{}' at index {}", self.token, self.index)
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, IntcodeError, ParseError};
+
+    #[test]
+    fn test_display() {
+        let error = IntcodeError::new(4, Some(98), ErrorKind::UnknownOpcode);
+        assert_eq!(error.to_string(), "unknown opcode at pc 4 (instruction 98)");
+
+        let error = IntcodeError::new(20, None, ErrorKind::PcOutOfRange(20));
+        assert_eq!(error.to_string(), "pc 20 is out of range at pc 20");
+
+        let error = ParseError { index : 3, token : String::from("4294967296") };
+        assert_eq!(error.to_string(), "invalid cell '4294967296' at index 3");
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+
+/// What is needed to undo one executed instruction.
+#[derive(Debug, Clone)]
+pub(crate) struct UndoRecord<T> {
+    pub(crate) pc : usize,
+    pub(crate) relative_base : i64,
+    pub(crate) finished : bool,
+    pub(crate) last_output : T,
+    pub(crate) output_len : usize,
+    /// The input value the instruction consumed, if any.
+    pub(crate) input : Option<T>,
+    /// Overwritten cells as `(address, old value)`, in write order.
+    pub(crate) writes : Vec<(usize, T)>,
+}
+
+impl<T> UndoRecord<T> {
+    pub(crate) fn wrote(&self, address : usize) -> bool {
+        self.writes.iter().any(|(written, _)| *written == address)
+    }
+}
+
+/// Undo log of the most recently executed instructions, used by
+/// `Automaton::step_back` and friends. When a limit is set the oldest
+/// records are dropped, so the machine can only be rewound that far.
+#[derive(Debug, Clone)]
+pub(crate) struct History<T> {
+    records : VecDeque<UndoRecord<T>>,
+    limit : Option<usize>,
+}
+
+impl<T> History<T> {
+    pub(crate) fn new(limit : Option<usize>) -> History<T> {
+        History { records : VecDeque::new(), limit }
+    }
+
+    pub(crate) fn push(&mut self, record : UndoRecord<T>) {
+        match self.limit {
+            Some(0) => return,
+            Some(limit) if self.records.len() == limit => { self.records.pop_front(); },
+            _ => (),
+        }
+        self.records.push_back(record);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<UndoRecord<T>> {
+        self.records.pop_back()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// How many records have to be undone to revert the latest write to `address`.
+    pub(crate) fn steps_to_write(&self, address : usize) -> Option<usize> {
+        self.records.iter().rev().position(|record| record.wrote(address)).map(|index| index + 1)
+    }
+}
@@ -0,0 +1,53 @@
+//! Intcode interpreter shared by the Advent of Code 2019 puzzles.
+//!
+//! The `Automaton` loads a comma-separated program image and executes it,
+//! either to the `99` (halt) instruction or pausing whenever it needs input
+//! or produces output. Memory cells are `i64` by
+//! default; enable the `bigint` feature to run on arbitrary-precision cells.
+//! The `async` feature adds `AsyncAutomaton`, which reads its input from a
+//! stream and writes its output to a sink.
+
+mod amplifier;
+mod ascii;
+mod assembler;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod automaton;
+mod cell;
+mod cfg;
+mod compiled;
+mod coverage;
+mod debugger;
+mod disassembler;
+mod error;
+mod history;
+mod memory;
+mod network;
+mod profiler;
+mod robot;
+mod snapshot;
+mod threaded;
+mod tracer;
+mod watchpoint;
+
+pub use amplifier::{permutations, AmplifierChain};
+pub use ascii::{decode_ascii, encode_line, AsciiAutomaton, AsciiOutput};
+pub use assembler::{assemble, assemble_cells, AssembleError};
+#[cfg(feature = "async")]
+pub use asynchronous::AsyncAutomaton;
+pub use automaton::{get_instruction_def, parse_program, Automaton, Instruction, InstructionDef, Parameter, ParameterMode, Status, INSTRUCTION_SET};
+pub use cell::Cell;
+pub use cfg::{BasicBlock, BlockError, ControlFlowGraph, Successor};
+pub use compiled::CompiledAutomaton;
+pub use coverage::Coverage;
+pub use debugger::Debugger;
+pub use disassembler::{disassemble, disassemble_with, listing, Line};
+pub use error::{ErrorKind, IntcodeError, ParseError};
+pub use memory::Memory;
+pub use network::{Network, NetworkEvent, Packet};
+pub use profiler::Profile;
+pub use robot::HullRobot;
+pub use snapshot::Snapshot;
+pub use threaded::ThreadGroup;
+pub use tracer::{JsonTracer, LogTracer, TraceEvent, Tracer};
+pub use watchpoint::{WatchEvent, WatchKind, Watchpoint};
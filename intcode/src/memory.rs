@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use crate::{Cell, ErrorKind};
+
+/// Addresses below this bound are kept in one contiguous vector that grows on demand.
+const DENSE_LIMIT : usize = 1 << 16;
+/// Number of cells in a page of the sparse store used above `DENSE_LIMIT`.
+const PAGE_SIZE : usize = 1024;
+
+/// Memory of an Intcode machine.
+///
+/// The loaded image and any addresses near it live in a vector that grows
+/// transparently; very high addresses are stored in sparse pages so a single
+/// far write does not allocate everything in between. Unwritten cells read
+/// as zero. An optional limit bounds the addressable range.
+#[derive(Debug, Clone)]
+pub struct Memory<T> {
+    dense : Vec<T>,
+    pages : HashMap<usize, Vec<T>>,
+    limit : Option<usize>,
+    image_len : usize,
+    zero : T,
+}
+
+impl<T : Cell> Memory<T> {
+    pub fn new(image : Vec<T>) -> Memory<T> {
+        Memory {
+            image_len: image.len(),
+            dense: image,
+            pages: HashMap::new(),
+            limit: None,
+            zero: T::zero(),
+        }
+    }
+
+    /// Sets the number of addressable cells; accesses at or above it are errors.
+    pub fn set_limit(&mut self, limit : Option<usize>) {
+        self.limit = limit;
+    }
+
+    pub fn get_limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// The contiguous part of memory, starting with the loaded image.
+    pub fn as_slice(&self) -> &[T] {
+        &self.dense
+    }
+
+    /// The number of cells in the image memory was created with.
+    pub fn get_image_len(&self) -> usize {
+        self.image_len
+    }
+
+    pub(crate) fn set_image_len(&mut self, image_len : usize) {
+        self.image_len = image_len;
+    }
+
+    pub fn read(&self, address : usize) -> Result<&T, ErrorKind> {
+        self.check_limit(address)?;
+        if address < self.dense.len() {
+            return Ok(&self.dense[address]);
+        }
+        Ok(self.pages.get(&(address / PAGE_SIZE))
+            .map(|page| &page[address % PAGE_SIZE])
+            .unwrap_or(&self.zero))
+    }
+
+    pub fn write(&mut self, address : usize, value : T) -> Result<(), ErrorKind> {
+        self.check_limit(address)?;
+        if address < self.dense.len() {
+            self.dense[address] = value;
+        } else if address < DENSE_LIMIT {
+            self.dense.resize(address + 1, T::zero());
+            self.dense[address] = value;
+        } else {
+            let page = self.pages.entry(address / PAGE_SIZE)
+                .or_insert_with(|| vec![T::zero(); PAGE_SIZE]);
+            page[address % PAGE_SIZE] = value;
+        }
+        Ok(())
+    }
+
+    /// Non-zero cells outside the contiguous part, ordered by address.
+    pub fn sparse_cells(&self) -> Vec<(usize, &T)> {
+        let mut cells : Vec<(usize, &T)> = self.pages.iter()
+            .flat_map(|(page, values)| values.iter().enumerate()
+                .filter(|(_, value)| **value != self.zero)
+                .map(move |(offset, value)| (page * PAGE_SIZE + offset, value)))
+            .collect();
+        cells.sort_by_key(|(address, _)| *address);
+        cells
+    }
+
+    fn check_limit(&self, address : usize) -> Result<(), ErrorKind> {
+        match self.limit {
+            Some(limit) if address >= limit => Err(ErrorKind::MemoryLimitExceeded(address)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ErrorKind, Memory};
+
+    #[test]
+    fn test_reads_beyond_image_are_zero() {
+        let memory : Memory<i64> = Memory::new(vec![1, 2, 3]);
+        assert_eq!(*memory.read(2).unwrap(), 3);
+        assert_eq!(*memory.read(3).unwrap(), 0);
+        assert_eq!(*memory.read(usize::MAX).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_writes_grow_memory() {
+        let mut memory : Memory<i64> = Memory::new(vec![1, 2, 3]);
+        memory.write(10, 7).unwrap();
+        assert_eq!(memory.as_slice(), &[1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn test_high_addresses_are_sparse() {
+        let mut memory : Memory<i64> = Memory::new(vec![1, 2, 3]);
+        memory.write(1 << 40, 9).unwrap();
+        assert_eq!(*memory.read(1 << 40).unwrap(), 9);
+        assert_eq!(*memory.read((1 << 40) + 1).unwrap(), 0);
+        assert_eq!(memory.as_slice().len(), 3);
+        memory.write(1 << 20, 8).unwrap();
+        assert_eq!(memory.sparse_cells(), vec![(1 << 20, &8), (1 << 40, &9)]);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut memory : Memory<i64> = Memory::new(vec![1, 2, 3]);
+        memory.set_limit(Some(8));
+        memory.write(7, 1).unwrap();
+        assert_eq!(memory.write(8, 1), Err(ErrorKind::MemoryLimitExceeded(8)));
+        assert_eq!(memory.read(100), Err(ErrorKind::MemoryLimitExceeded(100)));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{Automaton, Cell, IntcodeError, Status};
+
+/// Instructions a machine sharing a thread with others may execute before
+/// they get their turn, so a machine that never waits for input cannot
+/// starve the rest.
+pub(crate) const SLICE : usize = 10_000;
+
+/// A packet sent over the network: an output triple `(destination, x, y)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet<T = i64> {
+    pub destination : T,
+    pub x : T,
+    pub y : T,
+}
+
+/// Something that happened on the network during a round.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkEvent<T = i64> {
+    /// A machine sent a packet. Packets to an address outside the network
+    /// that is not the NAT's are dropped.
+    Sent { from : usize, packet : Packet<T> },
+    /// The network was idle and the NAT re-sent its last packet to address 0.
+    NatSent(Packet<T>),
+}
+
+/// Keeps the last packet sent to its address and wakes the network up
+/// with it when every machine is idle.
+struct Nat<T> {
+    address : T,
+    last : Option<Packet<T>>,
+}
+
+/// A network of machines running the same program, each booted with its
+/// address as first input. Output comes in triples `(destination, x, y)`
+/// that are delivered to the destination's input queue as `x, y`; a machine
+/// reading from an empty queue receives -1.
+///
+/// Machines take turns; a round gives every machine one turn, which lasts
+/// until it reads from an empty queue a second time, halts or has run for
+/// a while. The network is idle after a round in which no packets were
+/// sent and every machine read -1.
+pub struct Network<T = i64> {
+    machines : Vec<Automaton<T>>,
+    /// Output values of each machine that do not form a complete packet yet.
+    partial : Vec<Vec<T>>,
+    nat : Option<Nat<T>>,
+    /// Whether the network was idle after the last round.
+    idle : bool,
+}
+
+impl<T : Cell> Network<T> {
+    pub fn new(program : &[T], size : usize) -> Network<T> {
+        let machines = (0..size).map(|address| {
+            let mut automaton = Automaton::new();
+            let address = T::from_i64(address as i64).expect("Network address does not fit in a cell");
+            automaton.load_memory(program.to_vec()).push_input(address);
+            automaton
+        }).collect();
+        Network {
+            machines,
+            partial : vec![Vec::new(); size],
+            nat : None,
+            idle : false,
+        }
+    }
+
+    /// Captures packets sent to `address` in a NAT.
+    pub fn with_nat(&mut self, address : T) -> &mut Self {
+        self.nat = Some(Nat { address, last : None });
+
+        self
+    }
+
+    pub fn get_machine(&self, address : usize) -> &Automaton<T> {
+        &self.machines[address]
+    }
+
+    pub fn size(&self) -> usize {
+        self.machines.len()
+    }
+
+    /// Gives every machine one turn and routes the packets they send. When
+    /// the network turns out to be idle, the NAT sends its last packet.
+    pub fn round(&mut self) -> Result<Vec<NetworkEvent<T>>, IntcodeError> {
+        let mut events = Vec::new();
+        let mut idle = true;
+        for address in 0..self.machines.len() {
+            let starved = self.turn(address, &mut events)?;
+            idle &= starved || self.machines[address].is_finished();
+        }
+        idle &= events.is_empty() && self.machines.iter().all(|machine| machine.get_input().is_empty());
+        self.idle = idle;
+
+        if idle {
+            if let Some(packet) = self.nat.as_ref().and_then(|nat| nat.last.clone()) {
+                let packet = Packet { destination : T::zero(), ..packet };
+                self.deliver(&packet);
+                events.push(NetworkEvent::NatSent(packet));
+            }
+        }
+        Ok(events)
+    }
+
+    /// Runs rounds until `stop` returns true for an event, and returns that
+    /// event. Returns `None` once nothing can happen any more: all machines
+    /// halted, or the network is idle and there is no NAT packet to wake it.
+    pub fn run_until<F : FnMut(&NetworkEvent<T>) -> bool>(&mut self, mut stop : F)
+            -> Result<Option<NetworkEvent<T>>, IntcodeError> {
+        loop {
+            let events = self.round()?;
+            if let Some(event) = events.iter().find(|event| stop(event)) {
+                return Ok(Some(event.clone()));
+            }
+            let halted = self.machines.iter().all(|machine| machine.is_finished());
+            if halted || (events.is_empty() && self.idle) {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Whether the last round ended with the network idle.
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Runs one machine's turn. Returns whether it read from an empty queue
+    /// without sending anything.
+    fn turn(&mut self, address : usize, events : &mut Vec<NetworkEvent<T>>) -> Result<bool, IntcodeError> {
+        let mut starved = false;
+        let mut sent = false;
+        for _ in 0..SLICE {
+            let status = self.machines[address].step()?;
+            match status {
+                Status::NeedsInput if starved => break,
+                Status::NeedsInput => {
+                    self.machines[address].push_input(T::from_i64(-1).expect("-1 fits in every cell type"));
+                    starved = true;
+                },
+                Status::Output(value) => {
+                    self.partial[address].push(value);
+                    if self.partial[address].len() == 3 {
+                        let mut triple = std::mem::take(&mut self.partial[address]).into_iter();
+                        let (destination, x, y) = (triple.next().unwrap(), triple.next().unwrap(), triple.next().unwrap());
+                        let packet = Packet { destination, x, y };
+                        self.route(&packet);
+                        events.push(NetworkEvent::Sent { from : address, packet });
+                        sent = true;
+                    }
+                },
+                Status::Halted => break,
+                Status::Running | Status::Watchpoint(_) => (),
+            }
+        }
+        Ok(starved && !sent)
+    }
+
+    fn route(&mut self, packet : &Packet<T>) {
+        match self.nat.as_mut() {
+            Some(nat) if nat.address == packet.destination => nat.last = Some(packet.clone()),
+            _ => self.deliver(packet),
+        }
+    }
+
+    fn deliver(&mut self, packet : &Packet<T>) {
+        let machine = packet.destination.to_address().and_then(|address| self.machines.get_mut(address));
+        if let Some(machine) = machine {
+            machine.push_inputs(vec![packet.x.clone(), packet.y.clone()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assemble_cells, Network, NetworkEvent, Packet};
+
+    /// Machine 0 sends (1, 10, 20) at boot. Every machine forwards what it
+    /// receives to the NAT at 255, adding its own address to x.
+    const FORWARDER : &str = "
+                in [addr]
+                jt [addr], #loop
+                out #1
+                out #10
+                out #20
+        loop:   in [x]
+                eq [x], #-1, [empty]
+                jt [empty], #loop
+                in [y]
+                add [x], [addr], [x]
+                out #255
+                out [x]
+                out [y]
+                jt #1, #loop
+        addr:   db 0
+        x:      db 0
+        y:      db 0
+        empty:  db 0
+    ";
+
+    fn packet(destination : i64, x : i64, y : i64) -> Packet {
+        Packet { destination, x, y }
+    }
+
+    #[test]
+    fn test_routing_and_nat() {
+        let program = assemble_cells(FORWARDER).unwrap();
+        let mut network = Network::new(&program, 3);
+        network.with_nat(255);
+
+        let mut events = Vec::new();
+        let mut last_nat_y = None;
+        let repeated = network.run_until(|event| {
+            events.push(event.clone());
+            match event {
+                NetworkEvent::NatSent(packet) => {
+                    let repeated = last_nat_y == Some(packet.y);
+                    last_nat_y = Some(packet.y);
+                    repeated
+                },
+                _ => false,
+            }
+        }).unwrap();
+
+        assert_eq!(repeated, Some(NetworkEvent::NatSent(packet(0, 11, 20))));
+        assert_eq!(events, vec![
+            NetworkEvent::Sent { from : 0, packet : packet(1, 10, 20) },
+            NetworkEvent::Sent { from : 1, packet : packet(255, 11, 20) },
+            NetworkEvent::NatSent(packet(0, 11, 20)),
+            NetworkEvent::Sent { from : 0, packet : packet(255, 11, 20) },
+            NetworkEvent::NatSent(packet(0, 11, 20)),
+        ]);
+    }
+
+    #[test]
+    fn test_idle_without_nat() {
+        let program = assemble_cells(FORWARDER).unwrap();
+        let mut network = Network::new(&program, 2);
+        let first = network.run_until(|event| matches!(event, NetworkEvent::Sent { from : 1, .. })).unwrap();
+        assert_eq!(first, Some(NetworkEvent::Sent { from : 1, packet : packet(255, 11, 20) }));
+        // the packet to 255 is dropped and nothing else happens
+        assert_eq!(network.run_until(|_| true).unwrap(), None);
+        assert_eq!(network.size(), 2);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+use crate::{get_instruction_def, Automaton, Cell};
+
+/// Execution counts gathered while profiling is enabled on an `Automaton`.
+///
+/// Every executed instruction is counted against its address and opcode.
+/// Wall time is only accumulated inside `run` (and so `run_to_halt`);
+/// instructions executed with `step` are counted but not timed.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    per_address : HashMap<usize, u64>,
+    per_opcode : HashMap<i8, u64>,
+    instructions : u64,
+    elapsed : Duration,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub(crate) fn record(&mut self, pc : usize, opcode : i8) {
+        *self.per_address.entry(pc).or_insert(0) += 1;
+        *self.per_opcode.entry(opcode).or_insert(0) += 1;
+        self.instructions += 1;
+    }
+
+    pub(crate) fn add_time(&mut self, elapsed : Duration) {
+        self.elapsed += elapsed;
+    }
+
+    pub fn get_instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn get_elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn count_at(&self, address : usize) -> u64 {
+        self.per_address.get(&address).copied().unwrap_or(0)
+    }
+
+    pub fn count_of(&self, opcode : i8) -> u64 {
+        self.per_opcode.get(&opcode).copied().unwrap_or(0)
+    }
+
+    /// The `top` most executed addresses with their counts, hottest first.
+    pub fn hottest(&self, top : usize) -> Vec<(usize, u64)> {
+        let mut addresses : Vec<(usize, u64)> = self.per_address.iter()
+            .map(|(address, count)| (*address, *count))
+            .collect();
+        addresses.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        addresses.truncate(top);
+        addresses
+    }
+
+    /// A readable report: totals, counts per opcode and the `top` hottest
+    /// addresses disassembled from the machine's current memory.
+    pub fn report<T : Cell>(&self, automaton : &Automaton<T>, top : usize) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "{} instructions in {:?}", self.instructions, self.elapsed);
+
+        let mut opcodes : Vec<(i8, u64)> = self.per_opcode.iter().map(|(opcode, count)| (*opcode, *count)).collect();
+        opcodes.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        let _ = writeln!(report, "\nopcode       count   share");
+        for (opcode, count) in opcodes {
+            let mnemonic = get_instruction_def(opcode).map_or("?", |def| def.mnemonic);
+            let _ = writeln!(report, "{:<6} {:>11} {:>6.1}%", mnemonic, count, self.share(count));
+        }
+
+        let _ = writeln!(report, "\n      count   share  instruction");
+        for (address, count) in self.hottest(top) {
+            let instruction = match automaton.decode_at(address) {
+                Ok(instruction) => instruction.to_string(),
+                Err(error) => format!("<{}>", error.kind),
+            };
+            let _ = writeln!(report, "{:>11} {:>6.1}%  {:>5}: {}", count, self.share(count), address, instruction);
+        }
+        report
+    }
+
+    fn share(&self, count : u64) -> f64 {
+        if self.instructions == 0 {
+            return 0.0;
+        }
+        count as f64 * 100.0 / self.instructions as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Automaton;
+
+    #[test]
+    fn test_counts() {
+        // counts down from 3 in a loop: 3 iterations of jf, add, jt
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_profiling().load("1006,11,10,1001,11,-1,11,1105,1,0,99,3").unwrap();
+        automaton.run_to_halt().unwrap();
+
+        let profile = automaton.get_profile().unwrap();
+        assert_eq!(profile.get_instructions(), 11);
+        assert_eq!(profile.count_at(0), 4);
+        assert_eq!(profile.count_at(3), 3);
+        assert_eq!(profile.count_at(10), 1);
+        assert_eq!(profile.count_of(6), 4);
+        assert_eq!(profile.count_of(99), 1);
+        assert_eq!(profile.hottest(2), vec![(0, 4), (3, 3)]);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.enable_profiling().load("1002,4,3,4,33").unwrap();
+        automaton.run_to_halt().unwrap();
+        let report = automaton.get_profile().unwrap().report(&automaton, 5);
+        let lines : Vec<&str> = report.lines().collect();
+        assert!(lines[0].starts_with("2 instructions in "));
+        assert_eq!(&lines[1..], &[
+            "",
+            "opcode       count   share",
+            "mul              1   50.0%",
+            "hlt              1   50.0%",
+            "",
+            "      count   share  instruction",
+            "          1   50.0%      0: mul [4], #3, [4]",
+            "          1   50.0%      4: hlt",
+        ]);
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet};
+use day_3::Direction;
+use crate::{Automaton, Cell, IntcodeError, Status};
+
+/// A hull-painting robot driven by an Intcode program. The program reads
+/// the colour of the panel under the robot, 0 for black and 1 for white,
+/// then outputs the colour to paint it and the way to turn, 0 for left and
+/// 1 for right, after which the robot moves forward one panel.
+///
+/// The robot starts at (0, 0) facing up, on a hull that is all black
+/// except for panels set with `paint`.
+pub struct HullRobot<T = i64> {
+    automaton : Automaton<T>,
+    position : (i32,i32),
+    direction : Direction,
+    /// The colour of every panel that is not black by default; true is white.
+    panels : HashMap<(i32,i32), bool>,
+    painted : HashSet<(i32,i32)>,
+}
+
+impl<T : Cell> HullRobot<T> {
+    pub fn new(automaton : Automaton<T>) -> HullRobot<T> {
+        HullRobot {
+            automaton,
+            position : (0,0),
+            direction : Direction::Up,
+            panels : HashMap::new(),
+            painted : HashSet::new(),
+        }
+    }
+
+    pub fn get_automaton(&self) -> &Automaton<T> {
+        &self.automaton
+    }
+
+    /// Sets the colour of a panel before the robot starts. This does not
+    /// count as painting it.
+    pub fn paint(&mut self, position : (i32,i32), white : bool) -> &mut Self {
+        self.panels.insert(position, white);
+
+        self
+    }
+
+    pub fn get_position(&self) -> (i32,i32) {
+        self.position
+    }
+
+    pub fn get_direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn is_white(&self, position : (i32,i32)) -> bool {
+        self.panels.get(&position).copied().unwrap_or(false)
+    }
+
+    /// The number of panels the robot painted at least once.
+    pub fn painted_count(&self) -> usize {
+        self.painted.len()
+    }
+
+    /// Runs the program until it halts, moving the robot as it goes.
+    pub fn run(&mut self) -> Result<&mut Self, IntcodeError> {
+        let mut colour = None;
+        loop {
+            let status = self.automaton.run()?;
+            // an output can also arrive with a watchpoint pausing the machine
+            for value in self.automaton.take_output() {
+                match colour.take() {
+                    None => colour = Some(value != T::zero()),
+                    Some(white) => self.paint_and_move(white, value != T::zero()),
+                }
+            }
+            match status {
+                Status::NeedsInput => {
+                    let white = self.is_white(self.position);
+                    self.automaton.push_input(if white { T::one() } else { T::zero() });
+                },
+                Status::Halted => break,
+                Status::Output(_) | Status::Running | Status::Watchpoint(_) => (),
+            }
+        }
+        Ok(self)
+    }
+
+    /// The hull as text, one row per line from the top, with `#` for white
+    /// and a space for black panels. Only the area that holds white panels
+    /// is shown.
+    pub fn render(&self) -> String {
+        let white : Vec<&(i32,i32)> = self.panels.iter().filter(|(_, white)| **white).map(|(position, _)| position).collect();
+        if white.is_empty() {
+            return String::new();
+        }
+        let (min_x, max_x) = (white.iter().map(|p| p.0).min().unwrap(), white.iter().map(|p| p.0).max().unwrap());
+        let (min_y, max_y) = (white.iter().map(|p| p.1).min().unwrap(), white.iter().map(|p| p.1).max().unwrap());
+
+        let mut hull = String::new();
+        for y in (min_y..=max_y).rev() {
+            let row : String = (min_x..=max_x).map(|x| if self.is_white((x,y)) { '#' } else { ' ' }).collect();
+            hull.push_str(row.trim_end());
+            hull.push('\n');
+        }
+        hull
+    }
+
+    fn paint_and_move(&mut self, white : bool, right : bool) {
+        self.panels.insert(self.position, white);
+        self.painted.insert(self.position);
+        self.direction = if right { self.direction.turn_right() } else { self.direction.turn_left() };
+        self.position = self.direction.step(self.position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use day_3::Direction;
+    use crate::{assemble_cells, Automaton, HullRobot, WatchKind, Watchpoint};
+
+    /// Paints and turns as in the puzzle's example, then halts. The fifth
+    /// time it paints the panel it is on black only if it reads white.
+    const EXAMPLE : &str = "
+                in [colour]
+                out #1
+                out #0
+                in [colour]
+                out #0
+                out #0
+                in [colour]
+                out #1
+                out #0
+                in [colour]
+                out #1
+                out #0
+                in [colour]
+                eq [colour], #0, [colour]
+                out [colour]
+                out #1
+                in [colour]
+                out #1
+                out #0
+                in [colour]
+                out #1
+                out #0
+                hlt
+        colour: db 0
+    ";
+
+    fn robot(source : &str) -> HullRobot {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load_memory(assemble_cells(source).unwrap());
+        HullRobot::new(automaton)
+    }
+
+    #[test]
+    fn test_example() {
+        let mut robot = robot(EXAMPLE);
+        robot.run().unwrap();
+        assert_eq!(robot.painted_count(), 6);
+        assert_eq!(robot.get_position(), (0,1));
+        assert_eq!(robot.get_direction(), Direction::Left);
+        assert!(!robot.is_white((0,0)));
+        assert_eq!(robot.render(), "  #\n  #\n##\n");
+    }
+
+    #[test]
+    fn test_output_at_watchpoint() {
+        // pauses on the read of every operand, including those of `out`
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load_memory(assemble_cells(EXAMPLE).unwrap()).add_watchpoint(Watchpoint::new(0..100, WatchKind::Read));
+        let mut robot = HullRobot::new(automaton);
+        robot.run().unwrap();
+        assert_eq!(robot.painted_count(), 6);
+        assert_eq!(robot.get_position(), (0,1));
+    }
+
+    #[test]
+    fn test_starting_panel() {
+        // paints the panel it stands on black when it is white
+        let mut robot = robot("
+                in [colour]
+                eq [colour], #0, [colour]
+                out [colour]
+                out #1
+                hlt
+        colour: db 0
+        ");
+        robot.paint((0,0), true);
+        assert_eq!(robot.render(), "#\n");
+        robot.run().unwrap();
+        assert_eq!(robot.painted_count(), 1);
+        assert_eq!(robot.render(), "");
+        assert_eq!(robot.get_position(), (1,0));
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use crate::{Cell, Memory};
+
+const HEADER : &str = "intcode snapshot 1";
+
+/// The complete state of an `Automaton`, taken with `Automaton::snapshot`
+/// and put back with `Automaton::restore`.
+///
+/// Snapshots are plain text so they can be saved to a file and inspected:
+/// one `key value` line per register and comma-separated lists for the
+/// input queue, output buffer and memory.
+#[derive(Debug, Clone)]
+pub struct Snapshot<T = i64> {
+    pub(crate) memory : Memory<T>,
+    pub(crate) pc : usize,
+    pub(crate) relative_base : i64,
+    pub(crate) finished : bool,
+    pub(crate) input : VecDeque<T>,
+    pub(crate) output : Vec<T>,
+    pub(crate) last_output : T,
+    pub(crate) instruction_count : u64,
+}
+
+impl<T : Cell> Snapshot<T> {
+    pub fn save<P : AsRef<Path>>(&self, path : P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P : AsRef<Path>>(path : P) -> io::Result<Snapshot<T>> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn get_instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+}
+
+impl<T : Cell> fmt::Display for Snapshot<T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "pc {}", self.pc)?;
+        writeln!(f, "rb {}", self.relative_base)?;
+        writeln!(f, "finished {}", self.finished)?;
+        writeln!(f, "count {}", self.instruction_count)?;
+        writeln!(f, "last_output {}", self.last_output)?;
+        match self.memory.get_limit() {
+            Some(limit) => writeln!(f, "limit {}", limit)?,
+            None => writeln!(f, "limit none")?,
+        }
+        writeln!(f, "image {}", self.memory.get_image_len())?;
+        writeln!(f, "input {}", join(self.input.iter()))?;
+        writeln!(f, "output {}", join(self.output.iter()))?;
+        writeln!(f, "memory {}", join(self.memory.as_slice().iter()))?;
+        let sparse = self.memory.sparse_cells().iter()
+            .map(|(address, value)| format!("{}={}", address, value))
+            .collect::<Vec<String>>();
+        writeln!(f, "sparse {}", sparse.join(","))
+    }
+}
+
+impl<T : Cell> FromStr for Snapshot<T> {
+    type Err = io::Error;
+
+    fn from_str(text : &str) -> io::Result<Snapshot<T>> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("not an intcode snapshot"));
+        }
+        let mut snapshot = Snapshot {
+            memory : Memory::new(Vec::new()),
+            pc : 0,
+            relative_base : 0,
+            finished : false,
+            input : VecDeque::new(),
+            output : Vec::new(),
+            last_output : T::zero(),
+            instruction_count : 0,
+        };
+        let mut limit = None;
+        let mut image_len = None;
+        let mut sparse = Vec::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let (key, value) = match line.find(' ') {
+                Some(index) => (&line[..index], line[index + 1..].trim()),
+                None => (line.trim(), ""),
+            };
+            match key {
+                "pc" => snapshot.pc = parse(value)?,
+                "rb" => snapshot.relative_base = parse(value)?,
+                "finished" => snapshot.finished = parse(value)?,
+                "count" => snapshot.instruction_count = parse(value)?,
+                "last_output" => snapshot.last_output = parse(value)?,
+                "limit" if value == "none" => limit = None,
+                "limit" => limit = Some(parse(value)?),
+                "image" => image_len = Some(parse(value)?),
+                "input" => snapshot.input = parse_list(value)?.into_iter().collect(),
+                "output" => snapshot.output = parse_list(value)?,
+                "memory" => snapshot.memory = Memory::new(parse_list(value)?),
+                "sparse" => sparse = parse_list::<String>(value)?,
+                _ => return Err(invalid(&format!("unknown field '{}'", key))),
+            }
+        }
+        for cell in sparse {
+            let (address, value) = match cell.find('=') {
+                Some(index) => (parse(&cell[..index])?, parse(&cell[index + 1..])?),
+                None => return Err(invalid(&format!("'{}' is not an address=value pair", cell))),
+            };
+            snapshot.memory.write(address, value).map_err(|kind| invalid(&kind.to_string()))?;
+        }
+        snapshot.memory.set_limit(limit);
+        if let Some(image_len) = image_len {
+            snapshot.memory.set_image_len(image_len);
+        }
+        Ok(snapshot)
+    }
+}
+
+fn join<'a, T : fmt::Display + 'a, I : Iterator<Item = &'a T>>(values : I) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn parse<V : FromStr>(text : &str) -> io::Result<V> {
+    text.trim().parse().map_err(|_| invalid(&format!("'{}' is not a valid value", text)))
+}
+
+fn parse_list<V : FromStr>(text : &str) -> io::Result<Vec<V>> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    text.split(',').map(parse).collect()
+}
+
+fn invalid(message : &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use crate::{Automaton, Snapshot, Status};
+
+    #[test]
+    fn test_snapshot_text() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,9,4,9,1105,1,0,99,0,0").unwrap().push_inputs(vec![5, 6]);
+        automaton.run().unwrap();
+        automaton.write_memory(100_000, 3).unwrap();
+        assert_eq!(automaton.snapshot().to_string(), "\
+intcode snapshot 1
+pc 4
+rb 0
+finished false
+count 2
+last_output 5
+limit none
+image 10
+input 6
+output 5
+memory 3,9,4,9,1105,1,0,99,0,5
+sparse 100000=3
+");
+    }
+
+    #[test]
+    fn test_restore_branches() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,9,8,9,10,9,4,9,99,-1,8").unwrap();
+        assert_eq!(automaton.run().unwrap(), Status::NeedsInput);
+        let snapshot = automaton.snapshot();
+
+        automaton.set_input(8).run_to_halt().unwrap();
+        assert_eq!(automaton.get_output(), &[1]);
+
+        automaton.restore(&snapshot);
+        automaton.set_input(7).run_to_halt().unwrap();
+        assert_eq!(automaton.get_output(), &[0]);
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.load("3,9,8,9,10,9,4,9,99,-1,8").unwrap().run().unwrap();
+        let mut other = automaton.clone();
+        automaton.set_input(8).run_to_halt().unwrap();
+        other.set_input(9).run_to_halt().unwrap();
+        assert_eq!((automaton.get_last_output(), other.get_last_output()), (1, 0));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut automaton : Automaton = Automaton::new();
+        automaton.set_memory_limit(64).load("109,3,203,0,204,0,99").unwrap().push_inputs(vec![42]);
+        automaton.step().unwrap();
+        let path = env::temp_dir().join(format!("intcode-snapshot-{}.txt", std::process::id()));
+        automaton.snapshot().save(&path).unwrap();
+
+        let snapshot : Snapshot = Snapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(snapshot.get_pc(), 2);
+        let mut restored : Automaton = Automaton::new();
+        restored.restore(&snapshot).run_to_halt().unwrap();
+        assert_eq!(restored.get_output(), &[42]);
+        assert_eq!(restored.get_memory().get_limit(), Some(64));
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        assert!("1,2,3".parse::<Snapshot>().is_err());
+        assert!("intcode snapshot 1\npc x\n".parse::<Snapshot>().is_err());
+        assert!("intcode snapshot 1\ncolour red\n".parse::<Snapshot>().is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::{Automaton, Cell, ErrorKind, IntcodeError, Status};
+
+/// How long a waiting machine sleeps before checking its channel again.
+const POLL : Duration = Duration::from_millis(10);
+
+/// Bookkeeping shared by all machines of a `ThreadGroup`, used to tell a
+/// machine that waits for input from one that can never get any.
+struct Monitor {
+    state : Mutex<MonitorState>,
+    changed : Condvar,
+}
+
+struct MonitorState {
+    /// Machines that have not halted or failed.
+    running : usize,
+    /// Running machines blocked on an empty input channel.
+    waiting : usize,
+    /// Values sent on a channel and not yet received.
+    in_flight : usize,
+    deadlock : bool,
+}
+
+impl Monitor {
+    fn update<F : FnOnce(&mut MonitorState)>(&self, change : F) {
+        let mut state = self.state.lock().unwrap();
+        change(&mut state);
+        if state.waiting > 0 && state.waiting == state.running && state.in_flight == 0 {
+            state.deadlock = true;
+        }
+        self.changed.notify_all();
+    }
+}
+
+struct MachineSpec<T> {
+    program : Vec<T>,
+    inputs : Vec<T>,
+    output : Option<usize>,
+}
+
+/// Machines that each run on their own thread, with the output of one
+/// machine sent over a channel to the input of another. Pipelines and
+/// feedback loops of machines run concurrently.
+///
+/// When every machine that is still running waits for input and no value
+/// is on its way, `run` fails with `ErrorKind::Deadlock` instead of hanging.
+pub struct ThreadGroup<T = i64> {
+    machines : Vec<MachineSpec<T>>,
+}
+
+impl<T : Cell + Send + 'static> Default for ThreadGroup<T> {
+    fn default() -> Self {
+        ThreadGroup::new()
+    }
+}
+
+impl<T : Cell + Send + 'static> ThreadGroup<T> {
+    pub fn new() -> ThreadGroup<T> {
+        ThreadGroup { machines : Vec::new() }
+    }
+
+    /// Adds a machine running `program` and returns its index.
+    pub fn add_machine(&mut self, program : Vec<T>) -> usize {
+        self.machines.push(MachineSpec { program, inputs : Vec::new(), output : None });
+        self.machines.len() - 1
+    }
+
+    /// Sends everything machine `from` outputs to the input of machine `to`.
+    pub fn connect(&mut self, from : usize, to : usize) -> &mut Self {
+        self.machines[from].output = Some(to);
+
+        self
+    }
+
+    /// Queues an input value for a machine before it starts, e.g. a phase setting.
+    pub fn push_input(&mut self, machine : usize, value : T) -> &mut Self {
+        self.machines[machine].inputs.push(value);
+
+        self
+    }
+
+    /// Starts every machine on its own thread and waits for all of them to
+    /// stop. Returns the output of each machine, or the error of the first
+    /// machine, by index, that failed. Machines left waiting by a failed one
+    /// report a deadlock, so a deadlock is only returned if nothing else failed.
+    pub fn run(self) -> Result<Vec<Vec<T>>, IntcodeError> {
+        let monitor = Arc::new(Monitor {
+            state : Mutex::new(MonitorState { running : self.machines.len(), waiting : 0, in_flight : 0, deadlock : false }),
+            changed : Condvar::new(),
+        });
+        let (senders, receivers) : (Vec<Sender<T>>, Vec<Receiver<T>>) = self.machines.iter().map(|_| mpsc::channel()).unzip();
+
+        let handles : Vec<thread::JoinHandle<Result<Vec<T>, IntcodeError>>> = self.machines.into_iter()
+            .zip(receivers)
+            .map(|(spec, input)| {
+                let output = spec.output.map(|to| senders[to].clone());
+                let monitor = monitor.clone();
+                thread::spawn(move || {
+                    let result = run_machine(spec.program, spec.inputs, &input, output, &monitor);
+                    // values left in the channel will never be received
+                    monitor.update(move |state| {
+                        state.running -= 1;
+                        state.in_flight -= input.try_iter().count();
+                    });
+                    result
+                })
+            })
+            .collect();
+        drop(senders);
+
+        let results : Vec<Result<Vec<T>, IntcodeError>> = handles.into_iter()
+            .map(|handle| handle.join().expect("Intcode machine thread panicked"))
+            .collect();
+        let failure = results.iter()
+            .filter_map(|result| result.as_ref().err())
+            .find(|error| error.kind != ErrorKind::Deadlock);
+        match failure {
+            Some(error) => Err(error.clone()),
+            None => results.into_iter().collect(),
+        }
+    }
+}
+
+fn run_machine<T : Cell>(program : Vec<T>, inputs : Vec<T>, input : &Receiver<T>, output : Option<Sender<T>>,
+                         monitor : &Monitor) -> Result<Vec<T>, IntcodeError> {
+    let mut automaton = Automaton::new();
+    automaton.load_memory(program).push_inputs(inputs);
+    loop {
+        match automaton.run()? {
+            Status::Output(value) => {
+                if let Some(output) = output.as_ref() {
+                    // sending fails when the receiving machine has stopped
+                    monitor.update(|state| if output.send(value).is_ok() {
+                        state.in_flight += 1;
+                    });
+                }
+            },
+            Status::NeedsInput => {
+                let value = receive(input, monitor).map_err(|kind| automaton.error(kind))?;
+                automaton.push_input(value);
+            },
+            Status::Halted => return Ok(automaton.take_output()),
+            Status::Running | Status::Watchpoint(_) => (),
+        }
+    }
+}
+
+/// Waits for the next input value, failing when none can ever arrive.
+fn receive<T>(input : &Receiver<T>, monitor : &Monitor) -> Result<T, ErrorKind> {
+    let mut waiting = false;
+    loop {
+        match input.try_recv() {
+            Ok(value) => {
+                monitor.update(|state| {
+                    state.in_flight -= 1;
+                    if waiting {
+                        state.waiting -= 1;
+                    }
+                });
+                return Ok(value);
+            },
+            // no machine is left that could send anything
+            Err(TryRecvError::Disconnected) => return Err(ErrorKind::Deadlock),
+            Err(TryRecvError::Empty) => (),
+        }
+
+        if !waiting {
+            waiting = true;
+            monitor.update(|state| state.waiting += 1);
+        }
+        let state = monitor.state.lock().unwrap();
+        if state.deadlock {
+            return Err(ErrorKind::Deadlock);
+        }
+        let _ = monitor.changed.wait_timeout(state, POLL).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_program, ErrorKind, ThreadGroup};
+
+    #[test]
+    fn test_pipeline() {
+        // doubles its input
+        let program : Vec<i64> = parse_program("3,9,1002,9,2,9,4,9,99,0").unwrap();
+        let mut group : ThreadGroup = ThreadGroup::new();
+        let machines : Vec<usize> = (0..4).map(|_| group.add_machine(program.clone())).collect();
+        for pair in machines.windows(2) {
+            group.connect(pair[0], pair[1]);
+        }
+        group.push_input(machines[0], 3);
+        let outputs = group.run().unwrap();
+        assert_eq!(outputs[3], vec![48]);
+    }
+
+    #[test]
+    fn test_feedback_loop() {
+        let program : Vec<i64> = parse_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+            27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5").unwrap();
+        let mut group : ThreadGroup = ThreadGroup::new();
+        for phase in &[9, 8, 7, 6, 5] {
+            let machine = group.add_machine(program.clone());
+            group.push_input(machine, *phase);
+        }
+        for machine in 0..5 {
+            group.connect(machine, (machine + 1) % 5);
+        }
+        group.push_input(0, 0);
+        let outputs = group.run().unwrap();
+        assert_eq!(outputs[4].last(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        // two machines that both wait for the other to send first
+        let program : Vec<i64> = parse_program("3,0,4,0,99").unwrap();
+        let mut group : ThreadGroup = ThreadGroup::new();
+        let (a, b) = (group.add_machine(program.clone()), group.add_machine(program));
+        group.connect(a, b).connect(b, a);
+        assert_eq!(group.run().unwrap_err().kind, ErrorKind::Deadlock);
+    }
+
+    #[test]
+    fn test_failure_before_deadlock() {
+        // the second machine overflows, leaving the first waiting for input
+        let mut group : ThreadGroup = ThreadGroup::new();
+        let a = group.add_machine(parse_program("3,0,4,0,99").unwrap());
+        let b = group.add_machine(parse_program("1102,9223372036854775807,2,0,104,1,99").unwrap());
+        group.connect(b, a);
+        let error = group.run().unwrap_err();
+        assert_eq!((error.kind, error.pc), (ErrorKind::Overflow, 0));
+    }
+
+    #[test]
+    fn test_waits_for_stopped_machine() {
+        // the second machine reads twice, the first only sends once and halts
+        let mut group : ThreadGroup = ThreadGroup::new();
+        let a = group.add_machine(parse_program("104,1,99").unwrap());
+        let b = group.add_machine(parse_program("3,0,3,0,99").unwrap());
+        group.connect(a, b);
+        assert_eq!(group.run().unwrap_err().kind, ErrorKind::Deadlock);
+    }
+}
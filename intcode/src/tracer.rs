@@ -0,0 +1,166 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::rc::Rc;
+use crate::Instruction;
+
+/// One executed instruction, as reported to a `Tracer`.
+#[derive(Debug, Clone)]
+pub struct TraceEvent<T = i64> {
+    /// Number of instructions executed before this one.
+    pub count : u64,
+    pub pc : usize,
+    /// The relative base the operands were resolved against.
+    pub relative_base : i64,
+    pub instruction : Instruction<T>,
+    /// One entry per parameter: the value read, or the resolved address
+    /// for the parameter the instruction writes to.
+    pub operands : Vec<T>,
+    /// Memory cells written by the instruction as `(address, value)`.
+    pub writes : Vec<(usize, T)>,
+}
+
+/// Receives every instruction the `Automaton` executes.
+pub trait Tracer<T> {
+    fn trace(&mut self, event : &TraceEvent<T>);
+}
+
+/// Lets the caller keep a handle on a tracer that is owned by the machine.
+impl<T, R : Tracer<T>> Tracer<T> for Rc<RefCell<R>> {
+    fn trace(&mut self, event : &TraceEvent<T>) {
+        self.borrow_mut().trace(event);
+    }
+}
+
+/// Writes one JSON object per instruction, e.g.
+/// `{"count":0,"pc":0,"rb":0,"opcode":2,"mnemonic":"mul","params":[4,3,4],"modes":[0,1,0],"operands":[33,3,4],"writes":[[4,99]]}`
+pub struct JsonTracer<W> {
+    writer : W,
+    error : Option<io::Error>,
+}
+
+impl<W : Write> JsonTracer<W> {
+    pub fn new(writer : W) -> JsonTracer<W> {
+        JsonTracer { writer, error : None }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the writer, or the first error writing to it.
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.writer),
+        }
+    }
+}
+
+impl<T : Display, W : Write> Tracer<T> for JsonTracer<W> {
+    fn trace(&mut self, event : &TraceEvent<T>) {
+        if self.error.is_some() {
+            return;
+        }
+        let instruction = &event.instruction;
+        let mnemonic = crate::get_instruction_def(instruction.opcode).map_or("", |def| def.mnemonic);
+        let params = join(instruction.params.iter().map(|param| param.param.to_string()));
+        let modes = join(instruction.params.iter().map(|param| (param.mode as u8).to_string()));
+        let operands = join(event.operands.iter().map(|operand| operand.to_string()));
+        let writes = join(event.writes.iter().map(|(address, value)| format!("[{},{}]", address, value)));
+        let result = writeln!(self.writer,
+            "{{\"count\":{},\"pc\":{},\"rb\":{},\"opcode\":{},\"mnemonic\":\"{}\",\"params\":[{}],\"modes\":[{}],\"operands\":[{}],\"writes\":[{}]}}",
+            event.count, event.pc, event.relative_base, instruction.opcode, mnemonic, params, modes, operands, writes);
+        self.error = result.err();
+    }
+}
+
+/// Writes one compact line per instruction, e.g.
+/// `     0      0: mul [4], #3, [4]          33 3 4  [4]=99`
+pub struct LogTracer<W> {
+    writer : W,
+    error : Option<io::Error>,
+}
+
+impl<W : Write> LogTracer<W> {
+    pub fn new(writer : W) -> LogTracer<W> {
+        LogTracer { writer, error : None }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the writer, or the first error writing to it.
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.writer),
+        }
+    }
+}
+
+impl<T : Display, W : Write> Tracer<T> for LogTracer<W> {
+    fn trace(&mut self, event : &TraceEvent<T>) {
+        if self.error.is_some() {
+            return;
+        }
+        let operands = event.operands.iter().map(|operand| operand.to_string()).collect::<Vec<String>>().join(" ");
+        let writes = event.writes.iter().map(|(address, value)| format!("  [{}]={}", address, value)).collect::<String>();
+        let line = format!("{:>6} {:>6}: {:<24} {}{}", event.count, event.pc, event.instruction.to_string(), operands, writes);
+        let result = writeln!(self.writer, "{}", line.trim_end());
+        self.error = result.err();
+    }
+}
+
+fn join<I : Iterator<Item = String>>(values : I) -> String {
+    values.collect::<Vec<String>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::{Automaton, JsonTracer, LogTracer, TraceEvent, Tracer};
+
+    #[test]
+    fn test_json_tracer() {
+        let tracer = Rc::new(RefCell::new(JsonTracer::new(Vec::new())));
+        let mut automaton : Automaton = Automaton::new();
+        automaton.set_tracer(Box::new(tracer.clone())).load("1002,4,3,4,33").unwrap();
+        automaton.run_to_halt().unwrap();
+        assert_eq!(String::from_utf8(tracer.borrow().get_ref().clone()).unwrap(),
+            "{\"count\":0,\"pc\":0,\"rb\":0,\"opcode\":2,\"mnemonic\":\"mul\",\"params\":[4,3,4],\"modes\":[0,1,0],\"operands\":[33,3,4],\"writes\":[[4,99]]}\n\
+             {\"count\":1,\"pc\":4,\"rb\":0,\"opcode\":99,\"mnemonic\":\"hlt\",\"params\":[],\"modes\":[],\"operands\":[],\"writes\":[]}\n");
+    }
+
+    #[test]
+    fn test_log_tracer() {
+        let tracer = Rc::new(RefCell::new(LogTracer::new(Vec::new())));
+        let mut automaton : Automaton = Automaton::new();
+        automaton.set_tracer(Box::new(tracer.clone())).load("109,8,204,-3,99,3").unwrap().run_to_halt().unwrap();
+        assert_eq!(String::from_utf8(tracer.borrow().get_ref().clone()).unwrap(),
+            "     0      0: arb #8                   8\n\
+             \x20    1      2: out rb-3                 3\n\
+             \x20    2      4: hlt\n");
+    }
+
+    struct Recorder(Vec<(usize, Vec<(usize, i64)>)>);
+
+    impl Tracer<i64> for Recorder {
+        fn trace(&mut self, event : &TraceEvent<i64>) {
+            self.0.push((event.pc, event.writes.clone()));
+        }
+    }
+
+    #[test]
+    fn test_take_tracer() {
+        let recorder = Rc::new(RefCell::new(Recorder(Vec::new())));
+        let mut automaton : Automaton = Automaton::new();
+        automaton.set_tracer(Box::new(recorder.clone())).load("3,0,4,0,99").unwrap().set_input(7);
+        automaton.run_to_halt().unwrap();
+        assert!(automaton.take_tracer().is_some());
+        assert_eq!(recorder.borrow().0, vec![(0, vec![(0, 7)]), (2, vec![]), (4, vec![])]);
+        assert_eq!(automaton.get_instruction_count(), 3);
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::ops::Range;
+
+/// Which memory accesses a watchpoint reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+/// Watches a range of addresses for operand reads and/or writes.
+///
+/// Every hit is recorded as a `WatchEvent`; a pausing watchpoint also makes
+/// `step` return `Status::Watchpoint` once the instruction has completed.
+/// Fetching an instruction and its parameters is not counted as a read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range : Range<usize>,
+    pub kind : WatchKind,
+    pub pause : bool,
+}
+
+impl Watchpoint {
+    pub fn new(range : Range<usize>, kind : WatchKind) -> Watchpoint {
+        Watchpoint { range, kind, pause : true }
+    }
+
+    /// A watchpoint that only records events and never pauses execution.
+    pub fn logging(range : Range<usize>, kind : WatchKind) -> Watchpoint {
+        Watchpoint { range, kind, pause : false }
+    }
+
+    pub(crate) fn matches(&self, address : usize, write : bool) -> bool {
+        let kind = match self.kind {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        };
+        kind && self.range.contains(&address)
+    }
+}
+
+/// A watched address being read or written by the instruction at `pc`.
+/// `new` is only set for writes; for reads `old` is the value read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchEvent<T = i64> {
+    pub address : usize,
+    pub pc : usize,
+    pub old : T,
+    pub new : Option<T>,
+}
+
+impl<T : fmt::Display> fmt::Display for WatchEvent<T> {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match &self.new {
+            Some(new) => write!(f, "write [{}] {} -> {} at pc {}", self.address, self.old, new, self.pc),
+            None => write!(f, "read [{}] = {} at pc {}", self.address, self.old, self.pc),
+        }
+    }
+}